@@ -0,0 +1,220 @@
+use crate::format::{OutputMode, Radix};
+use crate::int_type::IntType;
+use crate::vm::{ShiftMode, DEFAULT_PAGE_SIZE};
+
+/// exit code if every expression was evaluated
+pub const EXIT_SUCCESS: i32 = 0;
+/// exit code if at least one expression could not be evaluated
+pub const EXIT_EVALUATION_ERROR: i32 = 1;
+/// exit code for invalid command line arguments
+pub const EXIT_USAGE: i32 = 2;
+/// exit code if a file could not be read or the terminal could not be used
+pub const EXIT_IO_ERROR: i32 = 3;
+
+pub const USAGE: &str = "\
+usage: ws_calculator [OPTIONS] [FILE | EXPR]
+       ws_calculator test [OPTIONS] [DIR | FILE]
+
+Evaluates EXPR, the expression in FILE, or every line read from stdin.
+Without arguments and with stdin connected to a terminal the interactive REPL is started.
+`test` runs every *.calc file in DIR (default: the current directory) and reports failed checks.
+
+options:
+  -e, --expr EXPR      evaluate EXPR, can be given more than once
+  -f, --format FORMAT  output format: all, dec, hex, oct, bin or json (default: all)
+  -w, --width BITS     word size: 8, 16, 32 or 64 (default: 64)
+  -s, --signed         interpret values as signed (default)
+  -u, --unsigned       interpret values as unsigned
+      --shift MODE     shift counts out of range: saturate (like ARM, default), mask (like x86) or error
+      --page-size N    page size in bytes for `to pages` (default: 4096)
+      --ruler          show the bit ruler for every result
+      --size           show every result as size in bytes, e.g. `4 MiB (4194304 bytes)`
+      --addr           show every result as address split into page number and offset
+      --bytes          show the bytes of every result in little and big endian order
+      --char           show every result as ASCII characters and as Unicode code point
+      --annotate FILE  append the result of every line of FILE as comment `# => ...`
+      --stdout         with --annotate, print the annotated file instead of rewriting it
+      --watch FILE     run FILE again whenever it is saved and highlight changed results
+      --tui            start the full-screen mode
+  -h, --help           print this help
+
+exit codes: 0 success, 1 evaluation error or failed test, 2 invalid arguments, 3 I/O error
+";
+
+/// How results are printed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    /// decimal, hex and binary, one per line
+    All,
+    /// only the given base
+    Radix(Radix),
+    /// one JSON object per evaluation
+    Json,
+}
+
+/// The parsed command line arguments.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+    pub expressions: Vec<String>,
+    /// the positional argument, either a file name or an expression
+    pub argument: Option<String>,
+    pub format: OutputFormat,
+    pub int_type: IntType,
+    pub shift_mode: ShiftMode,
+    pub page_size: i64,
+    pub output_modes: Vec<OutputMode>,
+    /// the file to annotate with the results of its lines
+    pub annotate: Option<String>,
+    pub stdout: bool,
+    /// the script to run again on every change
+    pub watch: Option<String>,
+    pub tui: bool,
+    /// `test` subcommand, the positional argument is the directory with the tests
+    pub test: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            expressions: Vec::new(),
+            argument: None,
+            format: OutputFormat::All,
+            int_type: IntType::default(),
+            shift_mode: ShiftMode::default(),
+            page_size: DEFAULT_PAGE_SIZE,
+            output_modes: Vec::new(),
+            annotate: None,
+            stdout: false,
+            watch: None,
+            tui: false,
+            test: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// parses the arguments without the program name.
+    /// Options that take a value accept it as next argument or after `=`, e.g. `--width=32`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut width = options.int_type.width;
+        let mut signed = options.int_type.signed;
+        let mut args = args.into_iter().peekable();
+        if args.next_if(|arg| arg == "test").is_some() {
+            options.test = true;
+        }
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next()).ok_or(format!("missing value for `{}`", name));
+            match name.as_str() {
+                "-e" | "--expr" => options.expressions.push(value()?),
+                "-f" | "--format" => {
+                    let format = value()?;
+                    options.format = match format.as_str() {
+                        "all" => OutputFormat::All,
+                        "json" => OutputFormat::Json,
+                        radix => OutputFormat::Radix(Radix::from_name(radix).ok_or(format!("unknown format `{}`", format))?),
+                    };
+                }
+                "-w" | "--width" => {
+                    let bits = value()?;
+                    width = bits.parse().ok().filter(|bits| IntType::new(*bits, true).is_some()).ok_or(format!("invalid width `{}`", bits))?;
+                }
+                "-s" | "--signed" => signed = true,
+                "-u" | "--unsigned" => signed = false,
+                "--shift" => {
+                    let mode = value()?;
+                    options.shift_mode = ShiftMode::from_name(&mode).ok_or(format!("unknown shift mode `{}`", mode))?;
+                }
+                "--page-size" => {
+                    let size = value()?;
+                    options.page_size = size.parse().ok().filter(|size| *size > 0).ok_or(format!("invalid page size `{}`", size))?;
+                }
+                "--ruler" => options.output_modes.push(OutputMode::Ruler),
+                "--size" => options.output_modes.push(OutputMode::Size),
+                "--addr" => options.output_modes.push(OutputMode::Addr),
+                "--bytes" => options.output_modes.push(OutputMode::Bytes),
+                "--char" => options.output_modes.push(OutputMode::Char),
+                "--annotate" => options.annotate = Some(value()?),
+                "--stdout" => options.stdout = true,
+                "--watch" => options.watch = Some(value()?),
+                "--tui" => options.tui = true,
+                "-h" | "--help" => options.help = true,
+                //a leading minus followed by a letter is an option, anything else like `-1` is an expression
+                flag if flag.starts_with("--") || flag.starts_with('-') && flag[1..].starts_with(|ch: char| ch.is_ascii_alphabetic()) => {
+                    return Err(format!("unknown option `{}`", flag));
+                }
+                _ if options.argument.is_none() => options.argument = Some(arg),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+        options.int_type = IntType::new(width, signed).unwrap();
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(Ok(Options::default()), parse(&[]));
+    }
+
+    #[test]
+    fn test_expressions() {
+        let options = parse(&["-e", "1 + 1", "--expr", "0xff << 4"]).unwrap();
+        assert_eq!(vec!["1 + 1".to_string(), "0xff << 4".to_string()], options.expressions);
+        assert_eq!(Some("-1 + 2".to_string()), parse(&["-1 + 2"]).unwrap().argument);
+    }
+
+    #[test]
+    fn test_format_and_type() {
+        let options = parse(&["--format", "hex", "-w", "16", "--unsigned"]).unwrap();
+        assert_eq!(OutputFormat::Radix(Radix::Hex), options.format);
+        assert_eq!(IntType { width: 16, signed: false }, options.int_type);
+        assert_eq!(OutputFormat::Json, parse(&["--format=json"]).unwrap().format);
+        assert_eq!(32, parse(&["--width=32"]).unwrap().int_type.width);
+        assert_eq!(ShiftMode::Mask, parse(&["--shift", "mask"]).unwrap().shift_mode);
+        assert_eq!(16384, parse(&["--page-size", "16384"]).unwrap().page_size);
+        assert_eq!(vec![OutputMode::Size, OutputMode::Addr], parse(&["--size", "--addr"]).unwrap().output_modes);
+        assert_eq!(vec![OutputMode::Bytes, OutputMode::Char], parse(&["--bytes", "--char"]).unwrap().output_modes);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let options = parse(&["--annotate", "notes.calc", "--stdout"]).unwrap();
+        assert_eq!(Some("notes.calc".to_string()), options.annotate);
+        assert!(options.stdout);
+        assert_eq!(Some("notes.calc".to_string()), parse(&["--watch=notes.calc"]).unwrap().watch);
+    }
+
+    #[test]
+    fn test_subcommand() {
+        let options = parse(&["test", "-w", "32", "regs"]).unwrap();
+        assert!(options.test);
+        assert_eq!(Some("regs".to_string()), options.argument);
+        assert!(!parse(&["-e", "test"]).unwrap().test);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse(&["--width", "12"]).is_err());
+        assert!(parse(&["--format", "roman"]).is_err());
+        assert!(parse(&["--shift", "wrap"]).is_err());
+        assert!(parse(&["--page-size", "0"]).is_err());
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["-e"]).is_err());
+        assert!(parse(&["a", "b"]).is_err());
+    }
+}
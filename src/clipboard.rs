@@ -0,0 +1,124 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Environment variable to select the clipboard backend.
+/// `osc52` selects the terminal escape sequence, everything else is run as command
+/// that receives the text on stdin, e.g. `xclip -selection clipboard`.
+pub const CLIPBOARD_ENV: &str = "CALC_CLIPBOARD";
+
+/// The ways text can be put into the system clipboard.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Clipboard {
+    /// an external program that reads the text from stdin (`xclip`, `wl-copy`, `pbcopy`, ...)
+    Command(String, Vec<String>),
+    /// the OSC 52 escape sequence, which lets the terminal set the clipboard.
+    /// This also works over SSH, as long as the local terminal supports it.
+    Osc52,
+}
+
+impl Clipboard {
+    /// picks the backend from `CALC_CLIPBOARD` or, if it is not set, from the environment
+    /// the calculator runs in.
+    pub fn detect() -> Self {
+        if let Ok(config) = std::env::var(CLIPBOARD_ENV) {
+            if let Some(clipboard) = Self::from_config(&config) {
+                return clipboard;
+            }
+        }
+        let is_set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+        if is_set("SSH_TTY") || is_set("SSH_CONNECTION") {
+            Clipboard::Osc52
+        } else if is_set("WAYLAND_DISPLAY") {
+            Clipboard::Command("wl-copy".to_string(), Vec::new())
+        } else if is_set("DISPLAY") {
+            Clipboard::Command("xclip".to_string(), vec!["-selection".to_string(), "clipboard".to_string()])
+        } else if cfg!(target_os = "macos") {
+            Clipboard::Command("pbcopy".to_string(), Vec::new())
+        } else {
+            Clipboard::Osc52
+        }
+    }
+
+    /// parses the value of `CALC_CLIPBOARD`. Returns `None` for an empty value.
+    pub fn from_config(config: &str) -> Option<Self> {
+        let mut words = config.split_whitespace();
+        match words.next()? {
+            "osc52" | "OSC52" => Some(Clipboard::Osc52),
+            program => Some(Clipboard::Command(program.to_string(), words.map(str::to_string).collect())),
+        }
+    }
+
+    pub fn copy(&self, text: &str) -> io::Result<()> {
+        match self {
+            Clipboard::Command(program, args) => {
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()?;
+                child.stdin.take().unwrap().write_all(text.as_bytes())?;
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(io::Error::other(format!("{} failed with {}", program, status)));
+                }
+                Ok(())
+            }
+            Clipboard::Osc52 => {
+                let mut stdout = io::stdout();
+                write!(stdout, "{}", osc52(text))?;
+                stdout.flush()
+            }
+        }
+    }
+}
+
+/// returns the escape sequence that asks the terminal to put `text` into the clipboard.
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("MHhmZg==", base64(b"0xff"));
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!("\x1b]52;c;MHhmZg==\x07", osc52("0xff"));
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(Some(Clipboard::Osc52), Clipboard::from_config("osc52"));
+        assert_eq!(
+            Some(Clipboard::Command("xclip".to_string(), vec!["-selection".to_string(), "clipboard".to_string()])),
+            Clipboard::from_config("xclip -selection clipboard")
+        );
+        assert_eq!(None, Clipboard::from_config("  "));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::format::{FormatOptions, OutputMode, Radix};
+use crate::scanner::unit_size;
+use crate::vm::ShiftMode;
+
+/// A line entered in the REPL: either one of the convenience commands or an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command<'a> {
+    Exit,
+    Copy(CopyTarget, FormatOptions),
+    Show(OutputMode),
+    Hide(OutputMode),
+    Shift(ShiftMode),
+    PageSize(i64),
+    /// load the register definitions in the file
+    Load(&'a str),
+    /// define the constants of the C header
+    Include(&'a str),
+    /// load the symbols of the ELF file
+    LoadElf(&'a str),
+    /// show the symbol the value of the expression points into
+    Lookup(&'a str),
+    /// show the bytes of the file: the file name and the expressions of the offset and the length
+    Hexdump(&'a str, &'a str, &'a str),
+    /// decode the value of the expression with the fields of the register
+    Decode(&'a str, &'a str),
+    /// build a value of the register from fields and their values
+    Encode(&'a str, Vec<(&'a str, &'a str)>),
+    Evaluate(&'a str),
+}
+
+/// What `copy` puts into the clipboard.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CopyTarget {
+    /// the last result in the given base
+    Value(Radix),
+    /// the last evaluated expression
+    Expression,
+}
+
+impl<'a> Command<'a> {
+    /// parses a line of input. Anything that is not a command is treated as expression.
+    pub fn parse(line: &'a str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("exit" | "quit") if words.next().is_none() => Ok(Command::Exit),
+            Some("copy") => Self::parse_copy(words),
+            Some(keyword @ ("show" | "hide")) => {
+                let usage = format!("usage: {} ruler|size|addr|bytes|char", keyword);
+                let mode = match (words.next(), words.next()) {
+                    (Some(name), None) => OutputMode::from_name(name).ok_or(usage)?,
+                    _ => return Err(usage),
+                };
+                Ok(if keyword == "show" { Command::Show(mode) } else { Command::Hide(mode) })
+            }
+            //`shift` without a valid mode is an expression, as it is a common name of a variable
+            Some("shift") => match (words.next().and_then(ShiftMode::from_name), words.next()) {
+                (Some(mode), None) => Ok(Command::Shift(mode)),
+                _ => Ok(Command::Evaluate(line)),
+            },
+            Some("pagesize") => match (words.next().map(|size| size.parse()), words.next()) {
+                (Some(Ok(size @ 1..)), None) => Ok(Command::PageSize(size)),
+                _ => Err("usage: pagesize BYTES".to_string()),
+            },
+            //like `shift`, these are common names of variables, so they are only commands if the arguments match
+            Some("load") => match file_name(line.trim_start()["load".len()..].trim()) {
+                Some(file_name) => Ok(Command::Load(file_name)),
+                None => Ok(Command::Evaluate(line)),
+            },
+            Some("include") => match file_name(line.trim_start()["include".len()..].trim()) {
+                Some(file_name) => Ok(Command::Include(file_name)),
+                None => Ok(Command::Evaluate(line)),
+            },
+            Some("load_elf") => match file_name(line.trim_start()["load_elf".len()..].trim()) {
+                Some(file_name) => Ok(Command::LoadElf(file_name)),
+                None => Ok(Command::Evaluate(line)),
+            },
+            //`lookup` followed by something that continues an operand is an expression with the variable `lookup`
+            Some("lookup") => match line.trim_start()["lookup".len()..].trim() {
+                expression if continues_operand(expression) => Ok(Command::Evaluate(line)),
+                expression => Ok(Command::Lookup(expression)),
+            },
+            Some(word) if word == "hexdump" || word.starts_with("hexdump(") => match Self::parse_hexdump(line.trim_start()["hexdump".len()..].trim()) {
+                Some((file_name, offset, len)) => Ok(Command::Hexdump(file_name, offset, len)),
+                None => Err("usage: hexdump(\"FILE\", OFFSET, LEN)".to_string()),
+            },
+            Some("decode") => match line.trim_start()["decode".len()..].trim_start().split_once(char::is_whitespace) {
+                Some((register, value)) if is_name(register) => Ok(Command::Decode(register, value.trim())),
+                _ => Ok(Command::Evaluate(line)),
+            },
+            Some("encode") => match (words.next(), words.map(|word| word.split_once('=')).collect::<Option<Vec<_>>>()) {
+                (Some(register), Some(fields)) if is_name(register) && fields.iter().all(|(field, _)| is_name(field)) => {
+                    Ok(Command::Encode(register, fields))
+                }
+                _ => Ok(Command::Evaluate(line)),
+            },
+            _ => Ok(Command::Evaluate(line)),
+        }
+    }
+
+    /// `hexdump("file", offset, len)`, the parens can be left out. The file name has to be quoted.
+    fn parse_hexdump(arguments: &'a str) -> Option<(&'a str, &'a str, &'a str)> {
+        match split_arguments(arguments)[..] {
+            [file_name, offset, len] => Some((quoted(file_name)?, offset, len)),
+            _ => None,
+        }
+    }
+
+    /// `copy bin|oct|dec|hex|expr [noprefix] [pad]`
+    fn parse_copy(mut words: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        const USAGE: &str = "usage: copy bin|oct|dec|hex|expr [noprefix] [pad]";
+        let target = match words.next() {
+            Some("expr") => CopyTarget::Expression,
+            Some(name) => CopyTarget::Value(Radix::from_name(name).ok_or(USAGE)?),
+            None => return Err(USAGE.to_string()),
+        };
+        let mut options = FormatOptions::default();
+        for option in words {
+            match option {
+                "noprefix" => options.prefix = false,
+                "prefix" => options.prefix = true,
+                "pad" => options.pad = true,
+                _ => return Err(format!("unknown option `{}`, {}", option, USAGE)),
+            }
+        }
+        Ok(Command::Copy(target, options))
+    }
+}
+
+/// splits the arguments of a command like `hexdump("file", offset, len)` at the commas that are neither in parens,
+/// which belong to calls in the arguments, nor in quotes. The parens around the arguments can be left out.
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let arguments = arguments.strip_prefix('(').and_then(|arguments| arguments.strip_suffix(')')).unwrap_or(arguments);
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (index, ch) in arguments.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => depth -= 1,
+            ',' if depth == 0 && !in_quotes => {
+                parts.push(arguments[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(arguments[start..].trim());
+    parts
+}
+
+/// returns the text between the quotes of `argument` like `"my regs.h"`, `None` if it is not quoted.
+fn quoted(argument: &str) -> Option<&str> {
+    let text = argument.strip_prefix('"')?.strip_suffix('"')?;
+    (!text.contains('"')).then_some(text)
+}
+
+/// returns the file name of the only argument of a command, either quoted or without spaces and quotes.
+fn file_name(argument: &str) -> Option<&str> {
+    quoted(argument).or_else(|| Some(argument).filter(|name| !name.is_empty() && !name.contains(|ch: char| ch.is_whitespace() || ch == '"')))
+}
+
+/// `true` if `rest` can follow an operand in an expression, e.g. a binary operator or a unit, or is empty.
+/// `&` directly followed by a name is the address of a symbol and `-` directly followed by the operand a negation.
+fn continues_operand(rest: &str) -> bool {
+    let word = rest.split(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_')).next().unwrap_or_default();
+    match rest.chars().next() {
+        None => true,
+        Some('&') => !rest[1..].starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_'),
+        Some('-') => rest[1..].starts_with(char::is_whitespace),
+        Some('+' | '*' | '/' | '%' | '!' | '=' | '<' | '>' | '|' | '^' | '?' | ':') => true,
+        _ => matches!(word, "and" | "AND" | "or" | "OR" | "xor" | "XOR" | "nor" | "NOR" | "mod" | "MOD" | "as" | "to") || unit_size(word).is_some(),
+    }
+}
+
+/// `true` for names of registers and fields, registers of a peripheral are qualified like `GPIOA.ODR`.
+fn is_name(word: &str) -> bool {
+    word.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_') && word.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit() {
+        assert_eq!(Ok(Command::Exit), Command::parse("exit"));
+        assert_eq!(Ok(Command::Exit), Command::parse("quit"));
+    }
+
+    #[test]
+    fn test_copy() {
+        assert_eq!(Ok(Command::Copy(CopyTarget::Value(Radix::Hex), FormatOptions::default())), Command::parse("copy hex"));
+        assert_eq!(
+            Ok(Command::Copy(CopyTarget::Value(Radix::Bin), FormatOptions { prefix: false, pad: true })),
+            Command::parse("copy bin noprefix pad")
+        );
+        assert_eq!(Ok(Command::Copy(CopyTarget::Expression, FormatOptions::default())), Command::parse("copy expr"));
+        assert!(Command::parse("copy").is_err());
+        assert!(Command::parse("copy hex upper").is_err());
+    }
+
+    #[test]
+    fn test_show_hide() {
+        assert_eq!(Ok(Command::Show(OutputMode::Ruler)), Command::parse("show ruler"));
+        assert_eq!(Ok(Command::Hide(OutputMode::Ruler)), Command::parse("hide ruler"));
+        assert_eq!(Ok(Command::Show(OutputMode::Size)), Command::parse("show size"));
+        assert_eq!(Ok(Command::Hide(OutputMode::Addr)), Command::parse("hide addr"));
+        assert_eq!(Ok(Command::Show(OutputMode::Bytes)), Command::parse("show bytes"));
+        assert_eq!(Ok(Command::Hide(OutputMode::Char)), Command::parse("hide char"));
+        assert!(Command::parse("show").is_err());
+        assert!(Command::parse("show colors").is_err());
+    }
+
+    #[test]
+    fn test_shift() {
+        assert_eq!(Ok(Command::Shift(ShiftMode::Mask)), Command::parse("shift mask"));
+        assert_eq!(Ok(Command::Evaluate("shift << 2")), Command::parse("shift << 2"));
+        assert_eq!(Ok(Command::Evaluate("shift")), Command::parse("shift"));
+    }
+
+    #[test]
+    fn test_page_size() {
+        assert_eq!(Ok(Command::PageSize(16384)), Command::parse("pagesize 16384"));
+        assert!(Command::parse("pagesize 0").is_err());
+        assert!(Command::parse("pagesize").is_err());
+    }
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(Ok(Command::Load("regs.toml")), Command::parse("load \"regs.toml\""));
+        assert_eq!(Ok(Command::Decode("CR1", "0x200c | 1")), Command::parse("decode CR1  0x200c | 1"));
+        assert_eq!(Ok(Command::Encode("CR1", vec![("UE", "1"), ("PS", "odd")])), Command::parse("encode CR1 UE=1 PS=odd"));
+        assert_eq!(Ok(Command::Encode("CR1", vec![])), Command::parse("encode CR1"));
+        assert_eq!(Ok(Command::Decode("GPIOA.ODR", "_")), Command::parse("decode GPIOA.ODR _"));
+        assert_eq!(Ok(Command::Evaluate("decode + 1")), Command::parse("decode + 1"));
+        assert_eq!(Ok(Command::Evaluate("encode == 2")), Command::parse("encode == 2"));
+        assert_eq!(Ok(Command::Evaluate("load")), Command::parse("load"));
+        assert_eq!(Ok(Command::Load("my regs.toml")), Command::parse("load \"my regs.toml\""));
+        assert_eq!(Ok(Command::Evaluate("load \"regs.toml")), Command::parse("load \"regs.toml"));
+        assert_eq!(Ok(Command::Include("regs.h")), Command::parse("include \"regs.h\""));
+        assert_eq!(Ok(Command::Include("my regs.h")), Command::parse("include \"my regs.h\""));
+        assert_eq!(Ok(Command::Include("regs.h")), Command::parse("include regs.h"));
+        assert_eq!(Ok(Command::Evaluate("include \"regs.h")), Command::parse("include \"regs.h"));
+        assert_eq!(Ok(Command::Evaluate("include a b")), Command::parse("include a b"));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(Ok(Command::Hexdump("dump.bin", "0x40", "64")), Command::parse("hexdump(\"dump.bin\", 0x40, 64)"));
+        assert_eq!(Ok(Command::Hexdump("a b.bin", "pow(2, 4)", "2 * 16")), Command::parse("hexdump \"a b.bin\", pow(2, 4), 2 * 16"));
+        assert!(Command::parse("hexdump(dump.bin, 0, 1)").is_err());
+        assert!(Command::parse("hexdump(\"dump.bin\", 0)").is_err());
+        assert_eq!(Ok(Command::Hexdump("a,b.bin", "0", "1")), Command::parse("hexdump(\"a,b.bin\", 0, 1)"));
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(Ok(Command::LoadElf("firmware.elf")), Command::parse("load_elf \"firmware.elf\""));
+        assert_eq!(Ok(Command::LoadElf("build 2/firmware.elf")), Command::parse("load_elf \"build 2/firmware.elf\""));
+        assert_eq!(Ok(Command::Evaluate("load_elf firmware.elf\"")), Command::parse("load_elf firmware.elf\""));
+        assert_eq!(Ok(Command::Lookup("0x8000_0150")), Command::parse("lookup 0x8000_0150"));
+        assert_eq!(Ok(Command::Lookup("&main + 4")), Command::parse("lookup &main + 4"));
+        assert_eq!(Ok(Command::Evaluate("lookup = 4")), Command::parse("lookup = 4"));
+        assert_eq!(Ok(Command::Evaluate("lookup")), Command::parse("lookup"));
+        assert_eq!(Ok(Command::Evaluate("lookup & 0x18")), Command::parse("lookup & 0x18"));
+        assert_eq!(Ok(Command::Evaluate("lookup && 1")), Command::parse("lookup && 1"));
+        assert_eq!(Ok(Command::Lookup("&g_buffer")), Command::parse("lookup &g_buffer"));
+        assert_eq!(Ok(Command::Evaluate("lookup - 1")), Command::parse("lookup - 1"));
+        assert_eq!(Ok(Command::Lookup("-0x10")), Command::parse("lookup -0x10"));
+        assert_eq!(Ok(Command::Evaluate("lookup / 2")), Command::parse("lookup / 2"));
+        assert_eq!(Ok(Command::Evaluate("lookup != 2")), Command::parse("lookup != 2"));
+        assert_eq!(Ok(Command::Evaluate("lookup and 1")), Command::parse("lookup and 1"));
+        assert_eq!(Ok(Command::Evaluate("lookup KiB")), Command::parse("lookup KiB"));
+        assert_eq!(Ok(Command::Lookup("main")), Command::parse("lookup main"));
+    }
+
+    #[test]
+    fn test_expression() {
+        assert_eq!(Ok(Command::Evaluate("1 + 2")), Command::parse("1 + 2"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+/// the number of bytes in a line of `hexdump`
+const BYTES_PER_LINE: usize = 16;
+
+/// returns the number of bytes and whether they are big endian for the name of a read function,
+/// e.g. `read_u32_be`. Without `_le` or `_be` the bytes are little endian. `None` for other names.
+pub fn read_function(name: &str) -> Option<(usize, bool)> {
+    let (name, big_endian) = match (name.strip_suffix("_le"), name.strip_suffix("_be")) {
+        (Some(name), _) => (name, false),
+        (_, Some(name)) => (name, true),
+        _ => (name, false),
+    };
+    let size = match name {
+        "read_u8" => 1,
+        "read_u16" => 2,
+        "read_u32" => 4,
+        "read_u64" => 8,
+        _ => return None,
+    };
+    Some((size, big_endian))
+}
+
+/// reads the unsigned integer of `size` bytes at `offset` of the file `file_name`.
+pub fn read(file_name: &str, offset: u64, size: usize, big_endian: bool) -> Result<u64, String> {
+    let bytes = read_bytes(file_name, offset, size)?;
+    if bytes.len() < size {
+        return Err(format!("{} has no {} bytes at offset {:#x}", file_name, size, offset));
+    }
+    Ok(value(&bytes, big_endian))
+}
+
+/// returns the unsigned integer made of `bytes`, at most 8.
+pub fn value(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
+
+/// shows `len` bytes at `offset` of the file `file_name` like `hexdump -C`, see `hexdump_bytes`.
+/// The dump ends early at the end of the file.
+pub fn hexdump(file_name: &str, offset: u64, len: usize) -> Result<String, String> {
+    let bytes = read_bytes(file_name, offset, len)?;
+    if bytes.is_empty() {
+        return Err(format!("{} has no bytes at offset {:#x}", file_name, offset));
+    }
+    Ok(hexdump_bytes(&bytes, offset))
+}
+
+/// shows `bytes` with 16 bytes per line: the offset of the line, the bytes in hex and as ASCII characters,
+/// where other bytes are shown as `.`.
+/// # Example
+/// ```text
+/// 00000040  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|
+/// ```
+pub fn hexdump_bytes(bytes: &[u8], offset: u64) -> String {
+    let lines: Vec<String> = bytes
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let (first, second) = hex.split_at(hex.len().min(BYTES_PER_LINE / 2));
+            let hex = format!("{:<23}  {:<23}", first.join(" "), second.join(" "));
+            let ascii: String = chunk.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' }).collect();
+            format!("{:08x}  {}  |{}|", offset + (index * BYTES_PER_LINE) as u64, hex, ascii)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// reads up to `len` bytes at `offset` of the file `file_name`, fewer at the end of the file.
+fn read_bytes(file_name: &str, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let error = |err: std::io::Error| format!("could not read {}: {}", file_name, err);
+    let mut file = File::open(file_name).map_err(error)?;
+    file.seek(SeekFrom::Start(offset)).map_err(error)?;
+    let mut bytes = Vec::with_capacity(len.min(1 << 20));
+    file.take(len as u64).read_to_end(&mut bytes).map_err(error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_function() {
+        assert_eq!(Some((1, false)), read_function("read_u8"));
+        assert_eq!(Some((4, false)), read_function("read_u32"));
+        assert_eq!(Some((4, false)), read_function("read_u32_le"));
+        assert_eq!(Some((8, true)), read_function("read_u64_be"));
+        assert_eq!(None, read_function("read_u24"));
+        assert_eq!(None, read_function("pow"));
+    }
+
+    #[test]
+    fn test_value() {
+        assert_eq!(0x12345678, value(&[0x78, 0x56, 0x34, 0x12], false));
+        assert_eq!(0x12345678, value(&[0x12, 0x34, 0x56, 0x78], true));
+        assert_eq!(u64::MAX, value(&[0xff; 8], true));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let bytes: Vec<u8> = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0Hello, world!".to_vec();
+        let lines: Vec<_> = hexdump_bytes(&bytes, 0x40).lines().map(str::to_string).collect();
+        assert_eq!("00000040  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|", lines[0]);
+        assert_eq!("00000050  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21           |Hello, world!|", lines[1]);
+    }
+
+    #[test]
+    fn test_read() {
+        let path = std::env::temp_dir().join(format!("ws_calculator_dump_{}.bin", std::process::id()));
+        std::fs::write(&path, [0u8, 1, 2, 3, 0xde, 0xad, 0xbe, 0xef]).unwrap();
+        let file_name = path.to_str().unwrap();
+        assert_eq!(Ok(0xefbeadde), read(file_name, 4, 4, false));
+        assert_eq!(Ok(0xdeadbeef), read(file_name, 4, 4, true));
+        assert_eq!(Ok(0x0302), read(file_name, 2, 2, false));
+        assert!(read(file_name, 6, 4, false).unwrap_err().contains("has no 4 bytes at offset 0x6"));
+        assert_eq!(1, hexdump(file_name, 4, 100).unwrap().lines().count());
+        assert!(hexdump(file_name, 8, 1).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(read(file_name, 0, 1, false).unwrap_err().starts_with("could not read"));
+    }
+}
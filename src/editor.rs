@@ -1,17 +1,69 @@
 use std::io::{self, Write};
 
-pub struct Editor {}
+use crate::parser::Parser;
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+pub struct Editor {
+    show_prompt: bool,
+}
 impl Editor {
     pub fn new() -> Self {
-        Self{}
+        Self{ show_prompt: true }
     }
 
+    /// an editor for input that does not come from a terminal, e.g. a pipe.
+    pub fn without_prompt() -> Self {
+        Self{ show_prompt: false }
+    }
+
+    /// reads the next complete input from stdin.
+    /// As long as the input is an incomplete expression (open parens, trailing binary operator)
+    /// the continuation prompt is shown and the next line is appended.
+    /// An empty continuation line submits the input as it is.
     pub fn read_line(&self) -> io::Result<String> {
         let mut buffer = String::new();
-        print!(">> ");
-        std::io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut buffer)?;
-        
+        let mut prompt = PROMPT;
+        loop {
+            if self.show_prompt {
+                print!("{}", prompt);
+                io::stdout().flush()?;
+            }
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if prompt == CONTINUATION_PROMPT && line.trim().is_empty() {
+                break;
+            }
+            buffer.push_str(&line);
+            if !is_incomplete(buffer.trim()) {
+                break;
+            }
+            prompt = CONTINUATION_PROMPT;
+        }
+
         Ok(buffer.trim().to_string())
     }
 }
+
+/// `true` if `input` is the beginning of an expression that needs more lines to be complete.
+pub fn is_incomplete(input: &str) -> bool {
+    !input.is_empty() && matches!(Parser::new(input).parse(), Err(err) if err.is_incomplete())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("(1 + 2"));
+        assert!(is_incomplete("(1 + 2)\n<<"));
+        assert!(!is_incomplete("(1 + 2)\n<< 3"));
+        assert!(!is_incomplete(""));
+        assert!(!is_incomplete("exit"));
+        assert!(!is_incomplete("1 + 2)"));
+    }
+}
@@ -0,0 +1,249 @@
+/// the section type of the full symbol table
+const SHT_SYMTAB: u32 = 2;
+/// the section type of the symbols needed for dynamic linking, used if there is no full symbol table
+const SHT_DYNSYM: u32 = 11;
+/// the machine of 32 bit ARM, where the lowest bit of the address of a Thumb function is set
+const EM_ARM: u16 = 40;
+
+/// A symbol of an ELF file, e.g. a function or a global variable.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub address: i64,
+    /// the size in bytes, 0 if it is unknown
+    pub size: i64,
+}
+
+/// reads the symbols of the ELF file `file_name`, see `parse`.
+pub fn load(file_name: &str) -> Result<Vec<Symbol>, String> {
+    let bytes = std::fs::read(file_name).map_err(|err| format!("could not read {}: {}", file_name, err))?;
+    parse(&bytes).map_err(|err| format!("{}: {}", file_name, err))
+}
+
+/// returns the defined functions, objects and labels of the symbol table of an ELF file, sorted by address.
+/// 32 and 64 bit files of both byte orders are supported.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Symbol>, String> {
+    if bytes.get(..4) != Some(b"\x7fELF") {
+        return Err("not an ELF file".to_string());
+    }
+    let elf = Reader {
+        bytes,
+        is_64: match bytes.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err("unknown ELF class".to_string()),
+        },
+        big_endian: match bytes.get(5) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err("unknown byte order".to_string()),
+        },
+    };
+    let machine = elf.u16(0x12)?;
+    let (section_offset, entry_size, count) = if elf.is_64 {
+        (elf.u64(0x28)?, elf.u16(0x3a)?, elf.u16(0x3c)?)
+    } else {
+        (elf.u32(0x20)? as u64, elf.u16(0x2e)?, elf.u16(0x30)?)
+    };
+    let sections: Vec<Section> = (0..count as u64)
+        .map(|index| elf.section(elf.entry(section_offset, index, entry_size as u64)?))
+        .collect::<Result<_, _>>()?;
+    let symbol_table = sections
+        .iter()
+        .find(|section| section.kind == SHT_SYMTAB)
+        .or_else(|| sections.iter().find(|section| section.kind == SHT_DYNSYM))
+        .ok_or("the file has no symbol table")?;
+    let strings = sections.get(symbol_table.link as usize).ok_or("the symbol table has no string table")?;
+    let symbol_size = if elf.is_64 { 24 } else { 16 };
+    let mut symbols = Vec::new();
+    for index in 1..symbol_table.size / symbol_size {
+        let offset = elf.entry(symbol_table.offset, index, symbol_size)?;
+        let (name, info, section, address, size) = if elf.is_64 {
+            (elf.u32(offset)?, elf.u8(offset + 4)?, elf.u16(offset + 6)?, elf.u64(offset + 8)?, elf.u64(offset + 16)?)
+        } else {
+            (elf.u32(offset)?, elf.u8(offset + 12)?, elf.u16(offset + 14)?, elf.u32(offset + 4)? as u64, elf.u32(offset + 8)? as u64)
+        };
+        //only defined symbols without a type, objects and functions, not sections and files
+        let kind = info & 0xf;
+        if section == 0 || kind > 2 {
+            continue;
+        }
+        let name = elf.string(elf.entry(strings.offset, name as u64, 1)?)?;
+        if name.is_empty() || name.starts_with('$') {
+            continue;
+        }
+        //Thumb functions have the lowest bit set to switch to the Thumb instruction set, it's not part of the address
+        let address = if machine == EM_ARM && kind == 2 { address & !1 } else { address };
+        symbols.push(Symbol { name: name.to_string(), address: address as i64, size: size as i64 });
+    }
+    symbols.sort_by_key(|symbol| symbol.address as u64);
+    Ok(symbols)
+}
+
+/// A section header of an ELF file, only with the fields needed for the symbol table.
+struct Section {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn read<const N: usize>(&self, offset: u64) -> Result<[u8; N], String> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.bytes.get(offset..offset.checked_add(N)?))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or(format!("the file ends before offset {:#x}", offset))
+    }
+
+    fn u8(&self, offset: u64) -> Result<u8, String> {
+        self.read::<1>(offset).map(|[byte]| byte)
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, String> {
+        self.read(offset).map(|bytes| if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, String> {
+        self.read(offset).map(|bytes| if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, String> {
+        self.read(offset).map(|bytes| if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+
+    /// returns the offset of the entry `index` of a table at `start` with entries of `size` bytes. It has to be in the
+    /// file, so the offsets of the fields of the entry can be added to it without overflowing.
+    fn entry(&self, start: u64, index: u64, size: u64) -> Result<u64, String> {
+        index
+            .checked_mul(size)
+            .and_then(|distance| start.checked_add(distance))
+            .filter(|offset| *offset < self.bytes.len() as u64)
+            .ok_or(format!("the file ends before offset {:#x}", start))
+    }
+
+    fn section(&self, offset: u64) -> Result<Section, String> {
+        Ok(if self.is_64 {
+            Section { kind: self.u32(offset + 4)?, offset: self.u64(offset + 0x18)?, size: self.u64(offset + 0x20)?, link: self.u32(offset + 0x28)? }
+        } else {
+            Section { kind: self.u32(offset + 4)?, offset: self.u32(offset + 0x10)? as u64, size: self.u32(offset + 0x14)? as u64, link: self.u32(offset + 0x18)? }
+        })
+    }
+
+    /// returns the null terminated string at `offset`.
+    fn string(&self, offset: u64) -> Result<&str, String> {
+        let bytes = usize::try_from(offset).ok().and_then(|offset| self.bytes.get(offset..)).ok_or("invalid symbol name")?;
+        let length = bytes.iter().position(|byte| *byte == 0).ok_or("invalid symbol name")?;
+        std::str::from_utf8(&bytes[..length]).map_err(|_| "invalid symbol name".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// builds an ELF file with a symbol table of `symbols`, given as name, address, size and type.
+    fn elf(is_64: bool, big_endian: bool, machine: u16, symbols: &[(&str, u64, u64, u8)]) -> Vec<u8> {
+        let word = |bytes: &mut Vec<u8>, value: u64, size: usize| {
+            let mut value = value.to_le_bytes()[..size].to_vec();
+            if big_endian {
+                value.reverse();
+            }
+            bytes.extend(value);
+        };
+        let mut strings = vec![0];
+        let mut table = vec![0; if is_64 { 24 } else { 16 }];
+        for (name, address, size, kind) in symbols {
+            let name_offset = strings.len() as u64;
+            strings.extend(name.bytes().chain([0]));
+            if is_64 {
+                word(&mut table, name_offset, 4);
+                table.extend([0x10 | kind, 0]);
+                word(&mut table, 1, 2);
+                word(&mut table, *address, 8);
+                word(&mut table, *size, 8);
+            } else {
+                word(&mut table, name_offset, 4);
+                word(&mut table, *address, 4);
+                word(&mut table, *size, 4);
+                table.extend([0x10 | kind, 0]);
+                word(&mut table, 1, 2);
+            }
+        }
+        let header_size = if is_64 { 64 } else { 52 };
+        let (strings_offset, table_offset) = (header_size, header_size + strings.len());
+        let sections_offset = table_offset + table.len();
+        let mut bytes = b"\x7fELF".to_vec();
+        bytes.extend([if is_64 { 2 } else { 1 }, if big_endian { 2 } else { 1 }, 1]);
+        bytes.resize(0x12, 0);
+        word(&mut bytes, machine as u64, 2);
+        bytes.resize(if is_64 { 0x28 } else { 0x20 }, 0);
+        word(&mut bytes, sections_offset as u64, if is_64 { 8 } else { 4 });
+        bytes.resize(if is_64 { 0x3a } else { 0x2e }, 0);
+        word(&mut bytes, if is_64 { 64 } else { 40 }, 2);
+        word(&mut bytes, 3, 2);
+        bytes.resize(header_size, 0);
+        bytes.extend(&strings);
+        bytes.extend(&table);
+        //the sections: null, the string table and the symbol table linked to it
+        for (kind, offset, size, link) in [(0, 0, 0, 0), (3, strings_offset, strings.len(), 0), (SHT_SYMTAB, table_offset, table.len(), 1)] {
+            word(&mut bytes, 0, 4);
+            word(&mut bytes, kind as u64, 4);
+            if is_64 {
+                bytes.extend([0; 16]);
+                word(&mut bytes, offset as u64, 8);
+                word(&mut bytes, size as u64, 8);
+                word(&mut bytes, link, 4);
+                bytes.extend([0; 20]);
+            } else {
+                bytes.extend([0; 8]);
+                word(&mut bytes, offset as u64, 4);
+                word(&mut bytes, size as u64, 4);
+                word(&mut bytes, link, 4);
+                bytes.extend([0; 12]);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse() {
+        let symbols = [("main", 0x1040, 0x20, 2), ("g_buffer", 0x4000, 256, 1), ("uart.c", 0, 0, 4), ("_start", 0x1000, 0, 0)];
+        let expected = vec![
+            Symbol { name: "_start".to_string(), address: 0x1000, size: 0 },
+            Symbol { name: "main".to_string(), address: 0x1040, size: 0x20 },
+            Symbol { name: "g_buffer".to_string(), address: 0x4000, size: 256 },
+        ];
+        assert_eq!(Ok(expected.clone()), parse(&elf(true, false, 62, &symbols)));
+        assert_eq!(Ok(expected), parse(&elf(false, true, 8, &symbols)));
+    }
+
+    #[test]
+    fn test_thumb() {
+        let symbols = parse(&elf(false, false, EM_ARM, &[("main", 0x8000_0141, 0x10, 2), ("table", 0x2000_0001, 4, 1)])).unwrap();
+        assert_eq!(0x2000_0001, symbols[0].address);
+        assert_eq!(0x8000_0140, symbols[1].address);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err("not an ELF file".to_string()), parse(b"MZ"));
+        let mut truncated = elf(true, false, 62, &[("main", 0x1040, 0x20, 2)]);
+        truncated.truncate(100);
+        assert!(parse(&truncated).is_err());
+        //a section header offset near the end of the address space must not overflow
+        let mut huge_offset = elf(true, false, 62, &[("main", 0x1040, 0x20, 2)]);
+        huge_offset[0x28..0x30].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        assert_eq!(Err("the file ends before offset 0xfffffffffffffff7".to_string()), parse(&huge_offset));
+        let mut huge_offset = elf(false, true, 8, &[("main", 0x1040, 0x20, 2)]);
+        huge_offset[0x20..0x24].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(Err("the file ends before offset 0xffffffff".to_string()), parse(&huge_offset));
+    }
+}
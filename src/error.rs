@@ -0,0 +1,120 @@
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+
+/// Errors produced while scanning, parsing or evaluating an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// The input ended while more tokens were expected, e.g. an unclosed paren
+    /// or a trailing binary operator.
+    UnexpectedEof,
+    /// A valid token that is not allowed at this position.
+    UnexpectedToken(Range<usize>, String),
+    /// Input the scanner could not turn into a token.
+    InvalidToken(Range<usize>, String),
+    /// `%` with zero as right operand, holds the range of the operator.
+    DivisionByZero(Range<usize>),
+    /// a variable that was never assigned
+    UnknownVariable(Range<usize>, String),
+    /// a symbol that was not loaded from an ELF file
+    UnknownSymbol(Range<usize>, String),
+    /// a call of a function that does not exist
+    UnknownFunction(Range<usize>, String),
+    /// a call of the function with the wrong number of arguments, holds the expected numbers
+    ArgumentCount(Range<usize>, String, RangeInclusive<usize>),
+    /// an operand or argument outside of the values an operation accepts, e.g. a negative exponent
+    InvalidArgument(Range<usize>, String),
+    /// an `assert` or `expect` of a script that did not hold, with the description of the mismatch
+    AssertionFailed(Range<usize>, String),
+}
+
+/// Something suspicious that did not stop the evaluation, e.g. a result that was truncated.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl Error {
+    /// Returns the byte range of the input this error refers to, if any.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Error::UnexpectedEof => None,
+            Error::UnexpectedToken(span, _)
+            | Error::InvalidToken(span, _)
+            | Error::DivisionByZero(span)
+            | Error::UnknownVariable(span, _)
+            | Error::UnknownSymbol(span, _)
+            | Error::UnknownFunction(span, _)
+            | Error::ArgumentCount(span, _, _)
+            | Error::InvalidArgument(span, _)
+            | Error::AssertionFailed(span, _) => Some(span.clone()),
+        }
+    }
+
+    /// moves the span of the error by `offset` bytes, for errors of an expression that is part of a larger input.
+    pub fn shifted(self, offset: usize) -> Self {
+        self.map_span(|span| span.start + offset..span.end + offset)
+    }
+
+    /// replaces the span of the error, for errors raised without knowing the position, e.g. by an operator.
+    pub fn at(self, span: Range<usize>) -> Self {
+        self.map_span(|_| span.clone())
+    }
+
+    fn map_span(self, f: impl Fn(Range<usize>) -> Range<usize>) -> Self {
+        match self {
+            Error::UnexpectedEof => Error::UnexpectedEof,
+            Error::UnexpectedToken(span, token) => Error::UnexpectedToken(f(span), token),
+            Error::InvalidToken(span, token) => Error::InvalidToken(f(span), token),
+            Error::DivisionByZero(span) => Error::DivisionByZero(f(span)),
+            Error::UnknownVariable(span, name) => Error::UnknownVariable(f(span), name),
+            Error::UnknownSymbol(span, name) => Error::UnknownSymbol(f(span), name),
+            Error::UnknownFunction(span, name) => Error::UnknownFunction(f(span), name),
+            Error::ArgumentCount(span, name, expected) => Error::ArgumentCount(f(span), name, expected),
+            Error::InvalidArgument(span, message) => Error::InvalidArgument(f(span), message),
+            Error::AssertionFailed(span, message) => Error::AssertionFailed(f(span), message),
+        }
+    }
+
+    /// returns a stable name of the kind of error, e.g. for JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::UnexpectedEof => "unexpected_eof",
+            Error::UnexpectedToken(..) => "unexpected_token",
+            Error::InvalidToken(..) => "invalid_token",
+            Error::DivisionByZero(_) => "division_by_zero",
+            Error::UnknownVariable(..) => "unknown_variable",
+            Error::UnknownSymbol(..) => "unknown_symbol",
+            Error::UnknownFunction(..) => "unknown_function",
+            Error::ArgumentCount(..) => "argument_count",
+            Error::InvalidArgument(..) => "invalid_argument",
+            Error::AssertionFailed(..) => "assertion_failed",
+        }
+    }
+
+    /// `true` if the error only means that the input is not complete yet.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, Error::UnexpectedEof)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnexpectedEof => write!(f, "unexpected end of input"),
+            Error::UnexpectedToken(_, token) => write!(f, "unexpected token `{}`", token),
+            Error::InvalidToken(_, token) => write!(f, "invalid token `{}`", token),
+            Error::DivisionByZero(_) => write!(f, "division by zero"),
+            Error::UnknownVariable(_, name) => write!(f, "unknown variable `{}`", name),
+            Error::UnknownSymbol(_, name) => write!(f, "unknown symbol `{}`", name),
+            Error::UnknownFunction(_, name) => write!(f, "unknown function `{}`", name),
+            Error::ArgumentCount(_, name, expected) if expected.start() != expected.end() => {
+                write!(f, "`{}` expects {} to {} arguments", name, expected.start(), expected.end())
+            }
+            Error::ArgumentCount(_, name, expected) if *expected.start() == 1 => write!(f, "`{}` expects 1 argument", name),
+            Error::ArgumentCount(_, name, expected) => write!(f, "`{}` expects {} arguments", name, expected.start()),
+            Error::InvalidArgument(_, message) => write!(f, "{}", message),
+            Error::AssertionFailed(_, message) => write!(f, "assertion failed: {}", message),
+        }
+    }
+}
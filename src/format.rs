@@ -0,0 +1,332 @@
+use crate::int_type::IntType;
+
+/// The bases a value can be displayed in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Radix {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+/// Options for turning a value into text.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FormatOptions {
+    /// prepend `0b`, `0o` or `0x`
+    pub prefix: bool,
+    /// pad bin, oct and hex output with leading zeros to the full word size
+    pub pad: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            prefix: true,
+            pad: false,
+        }
+    }
+}
+
+/// Additional views of a result that can be switched on in the REPL with `show` and off with `hide`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputMode {
+    /// the binary value in nibble groups with bit indices, see `ruler`
+    Ruler,
+    /// the value as a size in bytes, see `size`
+    Size,
+    /// the value as address split into page number and offset, see `address`
+    Addr,
+    /// the bytes of the value in little and big endian order, see `bytes`
+    Bytes,
+    /// the value as ASCII characters and as Unicode code point, see `characters`
+    Char,
+}
+
+impl OutputMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ruler" => Some(OutputMode::Ruler),
+            "size" => Some(OutputMode::Size),
+            "addr" => Some(OutputMode::Addr),
+            "bytes" => Some(OutputMode::Bytes),
+            "char" => Some(OutputMode::Char),
+            _ => None,
+        }
+    }
+
+    /// renders `value` of a type with `width` bits in this output mode.
+    pub fn render(&self, value: i64, width: u32, page_size: i64) -> String {
+        match self {
+            OutputMode::Ruler => ruler(value, width),
+            OutputMode::Size => size(value, width),
+            OutputMode::Addr => address(value, width, page_size),
+            OutputMode::Bytes => bytes(value, width),
+            OutputMode::Char => characters(value, width),
+        }
+    }
+}
+
+impl Radix {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bin" | "BIN" => Some(Radix::Bin),
+            "oct" | "OCT" => Some(Radix::Oct),
+            "dec" | "DEC" => Some(Radix::Dec),
+            "hex" | "HEX" => Some(Radix::Hex),
+            _ => None,
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            Radix::Bin => "0b",
+            Radix::Oct => "0o",
+            Radix::Dec => "",
+            Radix::Hex => "0x",
+        }
+    }
+
+    ///number of bits one digit represents, `None` for decimal.
+    fn bits_per_digit(&self) -> Option<u32> {
+        match self {
+            Radix::Bin => Some(1),
+            Radix::Oct => Some(3),
+            Radix::Dec => None,
+            Radix::Hex => Some(4),
+        }
+    }
+}
+
+/// formats `value` of type `int_type` in the given `radix`.
+/// Negative values are shown as two's complement of the type's width in every base except decimal.
+/// # Example
+/// ```
+/// let options = FormatOptions { prefix: true, pad: true };
+/// assert_eq!(format(0xff, Radix::Hex, &options, IntType { width: 16, signed: false }), "0x00ff");
+/// ```
+pub fn format(value: i64, radix: Radix, options: &FormatOptions, int_type: IntType) -> String {
+    let bits = value as u64 & int_type.mask();
+    let digits = match radix {
+        Radix::Bin => format!("{:b}", bits),
+        Radix::Oct => format!("{:o}", bits),
+        Radix::Dec => return int_type.to_i128(value).to_string(),
+        Radix::Hex => format!("{:x}", bits),
+    };
+    let digits = match radix.bits_per_digit() {
+        Some(bits) if options.pad => {
+            let len = int_type.width.div_ceil(bits) as usize;
+            format!("{:0>len$}", digits, len = len)
+        }
+        _ => digits,
+    };
+    if options.prefix {
+        format!("{}{}", radix.prefix(), digits)
+    } else {
+        digits
+    }
+}
+
+/// returns the value in hex followed by the decimal value, e.g. `0x1f40 (8000)`.
+pub fn summary(value: i64, int_type: IntType) -> String {
+    let options = FormatOptions::default();
+    format!("{} ({})", format(value, Radix::Hex, &options, int_type), format(value, Radix::Dec, &options, int_type))
+}
+
+/// shows the lowest `width` bits of `value` in nibble groups. The line above names the highest bit
+/// of every nibble, the line below marks the set bits.
+/// # Example
+/// ```
+/// assert_eq!(ruler(0x1c, 8), "7    3\n0001 1100\n   ^ ^^");
+/// ```
+pub fn ruler(value: i64, width: u32) -> String {
+    let mut indices = String::new();
+    let mut bits = String::new();
+    let mut marks = String::new();
+    for nibble in (0..width.div_ceil(4)).rev() {
+        let high = (nibble * 4 + 3).min(width - 1);
+        indices.push_str(&format!("{:<5}", high));
+        for bit in (nibble * 4..=high).rev() {
+            let is_set = value >> bit & 1 == 1;
+            bits.push(if is_set { '1' } else { '0' });
+            marks.push(if is_set { '^' } else { ' ' });
+        }
+        bits.push(' ');
+        marks.push(' ');
+    }
+    let lines = [indices.trim_end(), bits.trim_end(), marks.trim_end()];
+    lines.iter().filter(|line| !line.is_empty()).copied().collect::<Vec<_>>().join("\n")
+}
+
+/// shows the lowest `width` bits of `value` as a number of bytes in the largest binary unit that fits,
+/// with up to two truncated decimals, followed by the exact number of bytes.
+/// # Example
+/// ```
+/// assert_eq!(size(0x400000, 64), "4 MiB (4194304 bytes)");
+/// ```
+pub fn size(value: i64, width: u32) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    let bytes = value as u64 & (u64::MAX >> (64 - width));
+    let Some(exponent) = (1..=UNITS.len()).rev().find(|exponent| bytes >> (exponent * 10) > 0) else {
+        return format!("{} bytes", bytes);
+    };
+    //the decimals are truncated, rounding could show a value below a unit as the next unit like `1024 KiB`
+    let hundredths = (bytes as u128 * 100) >> (exponent * 10);
+    let scaled = format!("{}.{:02}", hundredths / 100, hundredths % 100);
+    let scaled = scaled.trim_end_matches('0').trim_end_matches('.');
+    format!("{} {} ({} bytes)", scaled, UNITS[exponent - 1], bytes)
+}
+
+/// shows the lowest `width` bits of `value` as address split into the page number and the offset in the page,
+/// the offset is padded to the digits of the largest offset.
+/// # Example
+/// ```
+/// assert_eq!(address(0x4001100c, 32, 4096), "page 0x40011 + offset 0x00c (page size 0x1000)");
+/// ```
+pub fn address(value: i64, width: u32, page_size: i64) -> String {
+    let address = value as u64 & (u64::MAX >> (64 - width));
+    let page_size = page_size as u64;
+    let digits = format!("{:x}", page_size - 1).len();
+    format!(
+        "page {:#x} + offset {:#0digits$x} (page size {:#x})",
+        address / page_size,
+        address % page_size,
+        page_size,
+        digits = digits + 2
+    )
+}
+
+/// shows the `width / 8` bytes of `value` in the order they are stored in memory, little and big endian.
+/// # Example
+/// ```
+/// assert_eq!(bytes(0x12345678, 32), "LE 78 56 34 12\nBE 12 34 56 78");
+/// ```
+pub fn bytes(value: i64, width: u32) -> String {
+    let count = width as usize / 8;
+    let little_endian: Vec<String> = value.to_le_bytes()[..count].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let big_endian: Vec<String> = little_endian.iter().rev().cloned().collect();
+    format!("LE {}\nBE {}", little_endian.join(" "), big_endian.join(" "))
+}
+
+/// shows the lowest `width` bits of `value` as byte string of ASCII characters without the leading zero bytes,
+/// the most significant byte first, and as Unicode code point with its UTF-8 and UTF-16 encodings.
+/// # Example
+/// ```
+/// assert_eq!(characters(0xe9, 32), "b\"\\xe9\"\nU+00E9 'é' UTF-8 c3 a9 UTF-16 00e9");
+/// ```
+pub fn characters(value: i64, width: u32) -> String {
+    let bits = value as u64 & (u64::MAX >> (64 - width));
+    let bytes = bits.to_be_bytes();
+    let first = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len() - 1);
+    let ascii: String = bytes[first..]
+        .iter()
+        .map(|byte| match byte {
+            b'\n' => "\\n".to_string(),
+            b'\r' => "\\r".to_string(),
+            b'\t' => "\\t".to_string(),
+            b'\0' => "\\0".to_string(),
+            b'"' | b'\\' => format!("\\{}", *byte as char),
+            byte if byte.is_ascii_graphic() || *byte == b' ' => (*byte as char).to_string(),
+            byte => format!("\\x{:02x}", byte),
+        })
+        .collect();
+    let unicode = match u32::try_from(bits).ok().map(|code_point| (code_point, char::from_u32(code_point))) {
+        Some((_, Some(ch))) => {
+            let utf8: Vec<String> = ch.to_string().bytes().map(|byte| format!("{:02x}", byte)).collect();
+            let utf16: Vec<String> = ch.encode_utf16(&mut [0; 2]).iter().map(|unit| format!("{:04x}", unit)).collect();
+            format!("U+{:04X} {:?} UTF-8 {} UTF-16 {}", ch as u32, ch, utf8.join(" "), utf16.join(" "))
+        }
+        Some((code_point @ 0xd800..=0xdfff, None)) => format!("U+{:04X} is a surrogate, not a character", code_point),
+        _ => format!("{:#x} is not a Unicode code point", bits),
+    };
+    format!("b\"{}\"\n{}", ascii, unicode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix() {
+        let options = FormatOptions::default();
+        assert_eq!("0xff", format(255, Radix::Hex, &options, IntType::I64));
+        assert_eq!("0b101", format(5, Radix::Bin, &options, IntType::I64));
+        assert_eq!("0o17", format(15, Radix::Oct, &options, IntType::I64));
+        assert_eq!("-3", format(-3, Radix::Dec, &options, IntType::I64));
+    }
+
+    #[test]
+    fn test_padding() {
+        let options = FormatOptions { prefix: false, pad: true };
+        let u16 = IntType { width: 16, signed: false };
+        assert_eq!("00ff", format(255, Radix::Hex, &options, u16));
+        assert_eq!("00000101", format(5, Radix::Bin, &options, IntType { width: 8, signed: false }));
+        assert_eq!("000017", format(15, Radix::Oct, &options, u16));
+        assert_eq!("42", format(42, Radix::Dec, &options, u16));
+    }
+
+    #[test]
+    fn test_width() {
+        let options = FormatOptions::default();
+        let i8 = IntType { width: 8, signed: true };
+        assert_eq!("0xff", format(-1, Radix::Hex, &options, i8));
+        assert_eq!("-1", format(-1, Radix::Dec, &options, i8));
+        let u64 = IntType { width: 64, signed: false };
+        assert_eq!("18446744073709551615", format(-1, Radix::Dec, &options, u64));
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!("0x1f40 (8000)", summary(8000, IntType::I64));
+        assert_eq!("0xff (-1)", summary(-1, IntType { width: 8, signed: true }));
+    }
+
+    #[test]
+    fn test_ruler() {
+        assert_eq!("7    3\n0001 1100\n   ^ ^^", ruler(0x1c, 8));
+        assert_eq!("15   11   7    3\n0000 0000 0000 0000", ruler(0, 16));
+        let lines: Vec<_> = ruler(i64::MIN, 64).lines().map(str::to_string).collect();
+        assert!(lines[0].starts_with("63   59   55"));
+        assert!(lines[0].ends_with("7    3"));
+        assert_eq!(79, lines[1].len());
+        assert_eq!("^", lines[2]);
+    }
+
+    #[test]
+    fn test_address() {
+        assert_eq!("page 0x40011 + offset 0x00c (page size 0x1000)", address(0x4001100c, 32, 4096));
+        assert_eq!("page 0x4 + offset 0x0000 (page size 0x10000)", address(0x40000, 64, 0x10000));
+        assert_eq!("page 0xfffffffffffff + offset 0xfff (page size 0x1000)", address(-1, 64, 4096));
+        assert_eq!("page 0x0 + offset 0x0 (page size 0x1)", address(0, 64, 1));
+    }
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!("LE 78 56 34 12\nBE 12 34 56 78", bytes(0x12345678, 32));
+        assert_eq!("LE ff\nBE ff", bytes(-1, 8));
+        assert_eq!("LE 01 00 00 00 00 00 00 80\nBE 80 00 00 00 00 00 00 01", bytes(i64::MIN + 1, 64));
+    }
+
+    #[test]
+    fn test_characters() {
+        assert_eq!("b\"A\"\nU+0041 'A' UTF-8 41 UTF-16 0041", characters(0x41, 64));
+        assert_eq!("b\"\\xe9\"\nU+00E9 'é' UTF-8 c3 a9 UTF-16 00e9", characters(0xe9, 32));
+        assert_eq!("b\"\\x01\\xf6\\0\"\nU+1F600 '😀' UTF-8 f0 9f 98 80 UTF-16 d83d de00", characters(0x1f600, 32));
+        assert_eq!("b\"\\0\"\nU+0000 '\\0' UTF-8 00 UTF-16 0000", characters(0, 8));
+        assert_eq!("b\"\\x1b\"\nU+001B '\\u{1b}' UTF-8 1b UTF-16 001b", characters(0x1b, 8));
+        assert_eq!("b\"AB\\n\\\"\"\n0x41420a22 is not a Unicode code point", characters(0x41420a22, 32));
+        assert_eq!("b\"\\xd8\\0\"\nU+D800 is a surrogate, not a character", characters(0xd800, 16));
+        assert_eq!("b\"\\xff\"\nU+00FF 'ÿ' UTF-8 c3 bf UTF-16 00ff", characters(-1, 8));
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!("4 MiB (4194304 bytes)", size(4 << 20, 64));
+        assert_eq!("1.5 KiB (1536 bytes)", size(1536, 64));
+        assert_eq!("1023 bytes", size(1023, 64));
+        assert_eq!("1.33 GiB (1431655765 bytes)", size(0x55555555, 32));
+        assert_eq!("255 bytes", size(-1, 8));
+        assert_eq!("15.99 EiB (18446744073709551615 bytes)", size(-1, 64));
+        assert_eq!("1023.99 KiB (1048575 bytes)", size(1048575, 64));
+        assert_eq!("1 MiB (1048576 bytes)", size(1048576, 64));
+    }
+}
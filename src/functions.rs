@@ -0,0 +1,325 @@
+use crate::error::Error;
+use crate::int_type::IntType;
+use std::ops::RangeInclusive;
+
+/// The built-in functions with their number of arguments.
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("pow", 2),
+    ("powmod", 3),
+    ("sext", 2),
+    ("zext", 2),
+    ("trunc", 2),
+    ("genmask", 2),
+    ("bit", 1),
+    ("field_prep", 2),
+    ("field_get", 2),
+    ("set_bit", 2),
+    ("clear_bit", 2),
+    ("toggle_bit", 2),
+    ("test_bit", 2),
+    ("align_up", 2),
+    ("align_down", 2),
+    ("is_aligned", 2),
+    ("page_index", 1),
+    ("offset_in_page", 1),
+    ("in_range", 3),
+    ("ranges_overlap", 4),
+    ("to_be", 1),
+    ("to_le", 1),
+];
+
+/// The built-in functions with a variable number of arguments and the least and most they take.
+const VARIADIC_FUNCTIONS: &[(&str, usize, usize)] = &[("from_bytes", 1, 8)];
+
+/// Other names of built-in functions, e.g. the macros of the Linux kernel, so code can be copied as it is.
+const ALIASES: &[(&str, &str)] = &[
+    ("GENMASK", "genmask"),
+    ("GENMASK_ULL", "genmask"),
+    ("BIT", "bit"),
+    ("BIT_ULL", "bit"),
+    ("FIELD_PREP", "field_prep"),
+    ("FIELD_GET", "field_get"),
+    ("ALIGN", "align_up"),
+    ("ALIGN_DOWN", "align_down"),
+    ("IS_ALIGNED", "is_aligned"),
+];
+
+/// returns the name of the built-in function `name` refers to.
+fn resolve(name: &str) -> &str {
+    ALIASES.iter().find(|(alias, _)| *alias == name).map_or(name, |(_, function)| function)
+}
+
+/// returns the numbers of arguments the built-in function `name` takes, `None` if there is no such function.
+pub fn arity(name: &str) -> Option<RangeInclusive<usize>> {
+    let name = resolve(name);
+    FUNCTIONS
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map(|(_, arity)| *arity..=*arity)
+        .or_else(|| VARIADIC_FUNCTIONS.iter().find(|(function, _, _)| *function == name).map(|(_, least, most)| *least..=*most))
+}
+
+/// calls the built-in function `name` with `args`, which are normalized to `int_type` and have the right count.
+/// `page_size` is used by the page helpers. Returns the result, which is not normalized yet, and whether the exact
+/// result did not fit into `int_type`. Errors don't have a position yet, see `Error::at`.
+pub fn call(name: &str, args: &[i64], int_type: IntType, page_size: i64) -> Result<(i64, bool), Error> {
+    let bits = |value: i64| value as u64 & int_type.mask();
+    let exact = |value: i128| (value as i64, !int_type.contains(value));
+    match (resolve(name), args) {
+        ("pow", &[base, exponent]) => power(base, exponent, int_type),
+        ("powmod", &[base, exponent, modulus]) => power_modulo(base, exponent, modulus, int_type).map(|value| (value, false)),
+        ("sext", &[value, bits]) => extend(value, bits, true, int_type).map(|value| (value, false)),
+        ("zext", &[value, bits]) => extend(value, bits, false, int_type).map(|value| (value, false)),
+        ("trunc", &[value, bits]) => extend(value, bits, int_type.signed, int_type).map(|value| (value, false)),
+        ("genmask", &[high, low]) => {
+            let (high, low) = (bit_number(high, 64, int_type)?, bit_number(low, 64, int_type)?);
+            if low > high {
+                return Err(Error::InvalidArgument(0..0, format!("the low bit {} is above the high bit {}", low, high)));
+            }
+            let mask = (u64::MAX >> (63 - high)) & (u64::MAX << low);
+            Ok((mask as i64, mask & !int_type.mask() != 0))
+        }
+        ("bit", &[bit]) => {
+            let bit = bit_number(bit, 64, int_type)?;
+            Ok((1 << bit, bit >= int_type.width))
+        }
+        ("field_prep", &[mask, value]) => {
+            let mask = field_mask(mask, int_type)?;
+            let field = bits(value) << mask.trailing_zeros();
+            if field & !mask != 0 {
+                return Err(Error::InvalidArgument(0..0, format!("value {:#x} does not fit into the field {:#x}", bits(value), mask)));
+            }
+            Ok((field as i64, false))
+        }
+        ("field_get", &[mask, value]) => {
+            let mask = field_mask(mask, int_type)?;
+            Ok((((bits(value) & mask) >> mask.trailing_zeros()) as i64, false))
+        }
+        //the bit number comes first like in the kernel, e.g. `set_bit(nr, addr)`
+        ("set_bit", &[bit, value]) => Ok((value | 1 << bit_number(bit, int_type.width, int_type)?, false)),
+        ("clear_bit", &[bit, value]) => Ok((value & !(1 << bit_number(bit, int_type.width, int_type)?), false)),
+        ("toggle_bit", &[bit, value]) => Ok((value ^ 1 << bit_number(bit, int_type.width, int_type)?, false)),
+        ("test_bit", &[bit, value]) => Ok((value >> bit_number(bit, int_type.width, int_type)? & 1, false)),
+        ("align_up", &[value, alignment]) => {
+            let alignment = alignment_of(alignment, int_type)?;
+            Ok(exact((int_type.to_i128(value) + alignment - 1).div_euclid(alignment) * alignment))
+        }
+        ("align_down", &[value, alignment]) => {
+            let alignment = alignment_of(alignment, int_type)?;
+            Ok(exact(int_type.to_i128(value).div_euclid(alignment) * alignment))
+        }
+        ("is_aligned", &[value, alignment]) => Ok(((int_type.to_i128(value) % alignment_of(alignment, int_type)? == 0) as i64, false)),
+        ("page_index", &[address]) => Ok((int_type.to_i128(address).div_euclid(page_size as i128) as i64, false)),
+        ("offset_in_page", &[address]) => Ok((int_type.to_i128(address).rem_euclid(page_size as i128) as i64, false)),
+        //ranges are given as start and length like in the kernel, the end is excluded
+        ("in_range", &[value, start, len]) => {
+            let (value, start) = (int_type.to_i128(value), int_type.to_i128(start));
+            Ok(((start..start + int_type.to_i128(len)).contains(&value) as i64, false))
+        }
+        ("ranges_overlap", &[start1, len1, start2, len2]) => {
+            let (start1, start2) = (int_type.to_i128(start1), int_type.to_i128(start2));
+            let (end1, end2) = (start1 + int_type.to_i128(len1), start2 + int_type.to_i128(len2));
+            Ok(((start1 < end2 && start2 < end1 && start1 < end1 && start2 < end2) as i64, false))
+        }
+        //the value is stored in a little endian machine, like on x86 and most ARM cores
+        ("to_be", &[value]) => Ok((swap_bytes(value, int_type.width), false)),
+        ("to_le", &[value]) => Ok((value, false)),
+        //the bytes come in the order the value is written, the most significant first
+        ("from_bytes", bytes) => {
+            let mut value = 0_u64;
+            for byte in bytes {
+                let byte = match int_type.to_i128(*byte) {
+                    byte @ 0..=0xff => byte as u64,
+                    byte => return Err(Error::InvalidArgument(0..0, format!("{} is not a byte", byte))),
+                };
+                value = value << 8 | byte;
+            }
+            Ok((value as i64, bytes.len() as u32 * 8 > int_type.width))
+        }
+        _ => unreachable!("unknown function `{}` with {} arguments", name, args.len()),
+    }
+}
+
+/// returns `base` to the power of `exponent`, wrapped around at 64 bits, and whether the exact result
+/// does not fit into `int_type`.
+pub fn power(base: i64, exponent: i64, int_type: IntType) -> Result<(i64, bool), Error> {
+    let exponent = exponent_of(exponent, int_type)?;
+    let (mut result, mut square, mut bits) = (1_i64, base, exponent);
+    while bits > 0 {
+        if bits & 1 == 1 {
+            result = result.wrapping_mul(square);
+        }
+        square = square.wrapping_mul(square);
+        bits >>= 1;
+    }
+    //every base other than -1, 0 and 1 overflows i128 long before 128, so the exponent can be capped keeping its parity
+    let capped = exponent.min(128 + (exponent & 1)) as u32;
+    let exact = int_type.to_i128(base).checked_pow(capped);
+    Ok((result, !exact.is_some_and(|exact| int_type.contains(exact))))
+}
+
+/// returns `base` to the power of `exponent` modulo `modulus` without overflowing, also for large exponents.
+pub fn power_modulo(base: i64, exponent: i64, modulus: i64, int_type: IntType) -> Result<i64, Error> {
+    let exponent = exponent_of(exponent, int_type)?;
+    let modulus = match int_type.to_i128(modulus) {
+        0 => return Err(Error::DivisionByZero(0..0)),
+        modulus if modulus < 0 => return Err(Error::InvalidArgument(0..0, "the modulus has to be positive".to_string())),
+        modulus => modulus as u128,
+    };
+    let (mut result, mut square, mut bits) = (1 % modulus, int_type.to_i128(base).rem_euclid(modulus as i128) as u128, exponent);
+    while bits > 0 {
+        if bits & 1 == 1 {
+            result = result * square % modulus;
+        }
+        square = square * square % modulus;
+        bits >>= 1;
+    }
+    Ok(result as i64)
+}
+
+/// reverses the order of the bytes of the lowest `width` bits of `value`, `width` is a multiple of 8.
+pub fn swap_bytes(value: i64, width: u32) -> i64 {
+    (value.swap_bytes() as u64 >> (64 - width)) as i64
+}
+
+/// treats the lowest `bits` of `value` as a value of that width and sign- or zero-extends it to 64 bits.
+pub fn extend(value: i64, bits: i64, signed: bool, int_type: IntType) -> Result<i64, Error> {
+    let bits = match int_type.to_i128(bits) {
+        bits @ 1..=64 => bits as u32,
+        _ => return Err(Error::InvalidArgument(0..0, "the bit width has to be between 1 and 64".to_string())),
+    };
+    let shift = 64 - bits;
+    Ok(if signed { value << shift >> shift } else { ((value as u64) << shift >> shift) as i64 })
+}
+
+/// checks that `bit` is a bit number below `limit`.
+fn bit_number(bit: i64, limit: u32, int_type: IntType) -> Result<u32, Error> {
+    match int_type.to_i128(bit) {
+        bit if (0..limit as i128).contains(&bit) => Ok(bit as u32),
+        bit => Err(Error::InvalidArgument(0..0, format!("bit {} is out of range, it has to be below {}", bit, limit))),
+    }
+}
+
+/// returns the bits of the field `mask`, which must not be 0.
+fn field_mask(mask: i64, int_type: IntType) -> Result<u64, Error> {
+    match mask as u64 & int_type.mask() {
+        0 => Err(Error::InvalidArgument(0..0, "the mask of the field must not be 0".to_string())),
+        mask => Ok(mask),
+    }
+}
+
+/// checks that `alignment` is positive.
+fn alignment_of(alignment: i64, int_type: IntType) -> Result<i128, Error> {
+    match int_type.to_i128(alignment) {
+        alignment if alignment > 0 => Ok(alignment),
+        _ => Err(Error::InvalidArgument(0..0, "the alignment has to be positive".to_string())),
+    }
+}
+
+fn exponent_of(exponent: i64, int_type: IntType) -> Result<u64, Error> {
+    u64::try_from(int_type.to_i128(exponent)).map_err(|_| Error::InvalidArgument(0..0, "the exponent must not be negative".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power() {
+        let u8 = IntType::new(8, false).unwrap();
+        assert_eq!(Ok((243, false)), power(3, 5, IntType::I64));
+        assert_eq!(Ok((1, false)), power(7, 0, IntType::I64));
+        assert_eq!(Ok((1 << 20, true)), power(2, 20, u8));
+        assert_eq!(Ok((-1, false)), power(-1, 1 << 40 | 1, IntType::I64));
+        assert_eq!(Ok((0, true)), power(2, 64, IntType::I64));
+        assert!(power(2, -1, IntType::I64).is_err());
+    }
+
+    #[test]
+    fn test_extend() {
+        assert_eq!(Ok(-2048), extend(0x800, 12, true, IntType::I64));
+        assert_eq!(Ok(0x800), extend(-2048, 12, false, IntType::I64));
+        assert_eq!(Ok(1), extend(0x7f01, 1, false, IntType::I64));
+        assert_eq!(Ok(-1), extend(-1, 64, true, IntType::I64));
+        assert!(extend(1, 65, true, IntType::I64).is_err());
+    }
+
+    #[test]
+    fn test_bits() {
+        let u8 = IntType::new(8, false).unwrap();
+        let call = |name: &str, args: &[i64]| call(name, args, IntType::I64, 4096).map(|(value, _)| value);
+        assert_eq!(Ok(0xff0), call("GENMASK", &[11, 4]));
+        assert_eq!(Ok(-1), call("genmask", &[63, 0]));
+        assert_eq!(Ok((0xff00, true)), super::call("genmask", &[15, 8], u8, 4096));
+        assert!(call("genmask", &[3, 4]).is_err());
+        assert_eq!(Ok(0x80), call("BIT", &[7]));
+        assert_eq!(Ok((0x100, true)), super::call("bit", &[8], u8, 4096));
+        assert!(call("bit", &[64]).is_err());
+        assert_eq!(Ok(0x350), call("FIELD_PREP", &[0xff0, 0x35]));
+        assert_eq!(
+            Err(Error::InvalidArgument(0..0, "value 0x100 does not fit into the field 0xff0".to_string())),
+            call("field_prep", &[0xff0, 0x100])
+        );
+        assert_eq!(Ok(0x35), call("FIELD_GET", &[0xff0, 0x1357]));
+        assert!(call("field_get", &[0, 1]).is_err());
+        assert_eq!(Ok(0x81), call("set_bit", &[7, 1]));
+        assert_eq!(Ok(0x01), call("clear_bit", &[7, 0x81]));
+        assert_eq!(Ok(0x80), call("toggle_bit", &[0, 0x81]));
+        assert_eq!(Ok(1), call("test_bit", &[7, 0x81]));
+        assert_eq!(Ok(0), call("test_bit", &[6, 0x81]));
+        assert!(super::call("set_bit", &[8, 1], u8, 4096).is_err());
+    }
+
+    #[test]
+    fn test_alignment() {
+        let u8 = IntType::new(8, false).unwrap();
+        let call = |name: &str, args: &[i64]| call(name, args, IntType::I64, 4096).map(|(value, _)| value);
+        assert_eq!(Ok(0x2000), call("align_up", &[0x1001, 4096]));
+        assert_eq!(Ok(0x1000), call("ALIGN", &[0x1000, 0x1000]));
+        assert_eq!(Ok((0x100, true)), super::call("align_up", &[0xf1, 0x10], u8, 4096));
+        assert_eq!(Ok(0x1000), call("align_down", &[0x1fff, 4096]));
+        assert_eq!(Ok(24), call("align_up", &[17, 12]));
+        assert_eq!(Ok(1), call("IS_ALIGNED", &[0x4000, 0x1000]));
+        assert_eq!(Ok(0), call("is_aligned", &[0x4004, 8]));
+        assert!(call("align_up", &[1, 0]).is_err());
+        assert_eq!(Ok(0x40011), call("page_index", &[0x4001100c]));
+        assert_eq!(Ok(0xc), call("offset_in_page", &[0x4001100c]));
+        assert_eq!(Ok((4, false)), super::call("page_index", &[0x1100c], IntType::I64, 0x4000));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let call = |name: &str, args: &[i64]| call(name, args, IntType::I64, 4096).map(|(value, _)| value);
+        assert_eq!(Ok(1), call("in_range", &[0x1fff, 0x1000, 0x1000]));
+        assert_eq!(Ok(0), call("in_range", &[0x2000, 0x1000, 0x1000]));
+        assert_eq!(Ok(1), call("ranges_overlap", &[0x1000, 0x1000, 0x1fff, 0x10]));
+        assert_eq!(Ok(0), call("ranges_overlap", &[0x1000, 0x1000, 0x2000, 0x10]));
+        assert_eq!(Ok(0), call("ranges_overlap", &[0x1000, 0, 0x1000, 0x10]));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let u16 = IntType::new(16, false).unwrap();
+        let call = |name: &str, args: &[i64]| call(name, args, IntType::I64, 4096).map(|(value, _)| value);
+        assert_eq!(Ok(0x7856341200000000), call("to_be", &[0x12345678]));
+        assert_eq!(Ok((0x3412, false)), super::call("to_be", &[0x1234], u16, 4096));
+        assert_eq!(Ok(0x12345678), call("to_le", &[0x12345678]));
+        assert_eq!(Ok(0x1234), call("from_bytes", &[0x12, 0x34]));
+        assert_eq!(Ok(-1), call("from_bytes", &[0xff; 8]));
+        assert_eq!(Ok((0x123456, true)), super::call("from_bytes", &[0x12, 0x34, 0x56], u16, 4096));
+        assert_eq!(Err(Error::InvalidArgument(0..0, "256 is not a byte".to_string())), call("from_bytes", &[0x12, 0x100]));
+        assert_eq!(Some(1..=8), arity("from_bytes"));
+        assert_eq!(Some(2..=2), arity("ALIGN"));
+        assert_eq!(0x12, swap_bytes(0x12, 8));
+    }
+
+    #[test]
+    fn test_power_modulo() {
+        assert_eq!(Ok(445), power_modulo(4, 13, 497, IntType::I64));
+        assert_eq!(Ok(2), power_modulo(2, i64::MAX, 3, IntType::I64));
+        assert_eq!(Ok(2), power_modulo(-1, 3, 3, IntType::I64));
+        let u64 = IntType::new(64, false).unwrap();
+        assert_eq!(Ok(0xfffffffffffffffe_u64 as i64), power_modulo(-2, 1, -1, u64));
+        assert_eq!(Err(Error::DivisionByZero(0..0)), power_modulo(2, 2, 0, IntType::I64));
+    }
+}
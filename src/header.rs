@@ -0,0 +1,274 @@
+use crate::vm::VM;
+
+/// the C types of `stdint.h` and the types of casts they become
+const C_TYPES: &[(&str, &str)] = &[
+    ("uint8_t", "u8"),
+    ("uint16_t", "u16"),
+    ("uint32_t", "u32"),
+    ("uint64_t", "u64"),
+    ("int8_t", "i8"),
+    ("int16_t", "i16"),
+    ("int32_t", "i32"),
+    ("int64_t", "i64"),
+];
+
+/// A constant of a C header: an object-like macro `#define NAME body` or an enum constant.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Constant {
+    pub name: String,
+    /// the expression of the value, `None` for function-like macros like `#define BIT(n) (1 << (n))`
+    pub body: Option<String>,
+    /// 1-based line of the definition
+    pub line: usize,
+}
+
+/// The outcome of including a header: the names and values of the constants that were defined and
+/// the constants that could not be evaluated with the reason.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Inclusion {
+    pub defined: Vec<(String, i64)>,
+    pub failed: Vec<(Constant, String)>,
+}
+
+/// reads the header `file_name` and defines its constants as variables of `vm`, see `include`.
+pub fn load(vm: &mut VM, file_name: &str) -> Result<Inclusion, String> {
+    let source = std::fs::read_to_string(file_name).map_err(|err| format!("could not read {}: {}", file_name, err))?;
+    Ok(include(vm, &source))
+}
+
+/// evaluates the constants of the C header `source` and defines them as variables of `vm`.
+/// Constants can refer to each other in any order, like macros are expanded when they are used.
+/// Preprocessor conditionals are ignored, so every `#define` is taken.
+pub fn include(vm: &mut VM, source: &str) -> Inclusion {
+    //the constants are evaluated in a copy, so the previous result of `vm` stays as it is
+    let mut scratch = vm.clone();
+    let mut inclusion = Inclusion::default();
+    let mut pending = Vec::new();
+    for constant in constants(source) {
+        match &constant.body {
+            Some(_) => pending.push((constant, String::new())),
+            None => inclusion.failed.push((constant, "function-like macros are not supported".to_string())),
+        }
+    }
+    //every pass defines the constants whose dependencies are defined, until a pass makes no progress
+    loop {
+        let count = pending.len();
+        let mut remaining = Vec::new();
+        for (constant, _) in pending {
+            match scratch.run(&c_expression(constant.body.as_deref().unwrap_or_default())) {
+                Ok(value) => {
+                    scratch.set_variable(&constant.name, value);
+                    vm.set_variable(&constant.name, value);
+                    inclusion.defined.push((constant.name, value));
+                }
+                Err(err) => remaining.push((constant, err.to_string())),
+            }
+        }
+        pending = remaining;
+        if pending.is_empty() || pending.len() == count {
+            break;
+        }
+    }
+    inclusion.failed.extend(pending);
+    inclusion.failed.sort_by_key(|(constant, _)| constant.line);
+    inclusion
+}
+
+/// returns the object-like macros and the enum constants of `source` in the order of their definition.
+/// Macros without a body like include guards are left out. An enum constant without a value is one more than the
+/// previous one, which is expressed as body `PREVIOUS + 1`.
+pub fn constants(source: &str) -> Vec<Constant> {
+    let source = strip_comments(source);
+    let mut constants = Vec::new();
+    let mut offset = 0;
+    let mut logical_line = String::new();
+    let mut logical_start = 0;
+    for line in source.split_inclusive('\n') {
+        if logical_line.is_empty() {
+            logical_start = offset;
+        }
+        offset += line.len();
+        let line = line.trim_end();
+        //a backslash at the end continues the line
+        if let Some(continued) = line.strip_suffix('\\') {
+            logical_line.push_str(continued);
+            logical_line.push(' ');
+            continue;
+        }
+        logical_line.push_str(line);
+        if let Some(constant) = define(&logical_line, line_of(&source, logical_start)) {
+            constants.push(constant);
+        }
+        logical_line.clear();
+    }
+    constants.extend(enums(&source));
+    constants.sort_by_key(|constant| constant.line);
+    constants
+}
+
+/// parses `#define NAME body`.
+fn define(line: &str, line_number: usize) -> Option<Constant> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("define")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let name_length = rest.find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_')).unwrap_or(rest.len());
+    let (name, body) = rest.split_at(name_length);
+    if name.is_empty() {
+        return None;
+    }
+    let body = match body.strip_prefix('(') {
+        Some(_) => None,
+        None if body.trim().is_empty() => return None,
+        None => Some(body.trim().to_string()),
+    };
+    Some(Constant { name: name.to_string(), body, line: line_number })
+}
+
+/// parses the constants of every `enum { A, B = 4, C }` in `source`.
+fn enums(source: &str) -> Vec<Constant> {
+    let mut constants = Vec::new();
+    let mut rest_start = 0;
+    while let Some(found) = source[rest_start..].find("enum") {
+        let start = rest_start + found;
+        rest_start = start + "enum".len();
+        let is_word_start = !source[..start].ends_with(|ch: char| ch.is_ascii_alphanumeric() || ch == '_');
+        let is_word_end = !source[rest_start..].starts_with(|ch: char| ch.is_ascii_alphanumeric() || ch == '_');
+        //the tag name is optional, but nothing else may be between `enum` and `{`
+        let Some(open) = source[rest_start..].find(['{', ';', '(', ')']).map(|open| rest_start + open) else { break };
+        let tag = source[rest_start..open].trim();
+        let is_tag = tag.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if !(is_word_start && is_word_end && is_tag && source[open..].starts_with('{')) {
+            continue;
+        }
+        let Some(close) = source[open..].find('}').map(|close| open + close) else { break };
+        let mut previous: Option<String> = None;
+        let mut entry_start = open + 1;
+        for entry in source[open + 1..close].split(',') {
+            let entry_offset = entry_start + entry.len() - entry.trim_start().len();
+            entry_start += entry.len() + 1;
+            let (name, value) = match entry.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+                None => (entry.trim(), None),
+            };
+            if name.is_empty() {
+                continue;
+            }
+            let body = value.unwrap_or_else(|| previous.as_ref().map_or("0".to_string(), |previous| format!("{} + 1", previous)));
+            constants.push(Constant { name: name.to_string(), body: Some(body), line: line_of(source, entry_offset) });
+            previous = Some(name.to_string());
+        }
+        rest_start = close;
+    }
+    constants
+}
+
+/// replaces the comments of `source` with spaces, keeping the newlines so offsets and lines stay the same.
+fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("/*").into_iter().chain(rest.find("//")).min() {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = if rest.starts_with("/*") { rest.find("*/").map_or(rest.len(), |end| end + 2) } else { rest.find('\n').unwrap_or(rest.len()) };
+        output.extend(rest[..end].chars().map(|ch| if ch == '\n' { '\n' } else { ' ' }));
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+/// translates a C expression into an expression of the calculator: `~` is the bitwise not `!` of the calculator,
+/// the logical not `!` is `not` and casts to the types of `stdint.h` become casts to `u8` and so on.
+pub fn c_expression(body: &str) -> String {
+    let mut expression = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '~' => expression.push('!'),
+            '!' if chars.peek() != Some(&'=') => {
+                if !expression.is_empty() && !expression.ends_with([' ', '(']) {
+                    expression.push(' ');
+                }
+                expression.push_str("not ");
+            }
+            ch => expression.push(ch),
+        }
+    }
+    for (c_type, int_type) in C_TYPES {
+        expression = expression.replace(&format!("({})", c_type), &format!("({})", int_type));
+    }
+    expression
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = r#"
+#ifndef REGS_H
+#define REGS_H
+
+/* base of the peripherals */
+#define PERIPH_BASE   0x40000000UL
+#define UART_BASE     (PERIPH_BASE + UART_OFFSET) // defined below
+#define UART_OFFSET   0x11000
+#define UART_CR1_UE   (1U << 13)
+#define UART_CR1_MASK (~(uint32_t)UART_CR1_UE)
+#define BIT(n)        (1UL << (n))
+#define LONG_VALUE    (0x10 | \
+                       0x01)
+#define NAME          "uart"
+
+typedef enum {
+    MODE_IDLE,
+    MODE_RX = 4,
+    MODE_TX, /* 5 */
+} uart_mode_t;
+
+#endif
+"#;
+
+    #[test]
+    fn test_constants() {
+        let constants = constants(HEADER);
+        let names: Vec<_> = constants.iter().map(|constant| constant.name.as_str()).collect();
+        assert_eq!(
+            vec!["PERIPH_BASE", "UART_BASE", "UART_OFFSET", "UART_CR1_UE", "UART_CR1_MASK", "BIT", "LONG_VALUE", "NAME", "MODE_IDLE", "MODE_RX", "MODE_TX"],
+            names
+        );
+        assert_eq!(Some("(PERIPH_BASE + UART_OFFSET)".to_string()), constants[1].body);
+        assert_eq!(7, constants[1].line);
+        assert_eq!(None, constants[5].body);
+        assert_eq!(Some("(0x10 |                         0x01)".to_string()), constants[6].body);
+        assert_eq!(Some("MODE_RX + 1".to_string()), constants[10].body);
+        assert_eq!(19, constants[10].line);
+    }
+
+    #[test]
+    fn test_include() {
+        let mut vm = VM::new();
+        vm.set_previous_result(42);
+        let inclusion = include(&mut vm, HEADER);
+        assert_eq!(Some(42), vm.previous_result());
+        assert_eq!(Ok(0x40011000), vm.run("UART_BASE"));
+        //like every cast, `(uint32_t)` only truncates the value, which is used at the current word size again
+        assert_eq!(Ok(!0x2000), vm.run("UART_CR1_MASK"));
+        assert_eq!(Ok(0x11), vm.run("LONG_VALUE"));
+        assert_eq!(Ok(5), vm.run("MODE_TX"));
+        assert_eq!(9, inclusion.defined.len());
+        let failed: Vec<_> = inclusion.failed.iter().map(|(constant, reason)| (constant.name.as_str(), reason.as_str())).collect();
+        assert_eq!(vec![("BIT", "function-like macros are not supported"), ("NAME", "unexpected token `\"uart\"`")], failed);
+    }
+
+    #[test]
+    fn test_c_expression() {
+        assert_eq!("!(u32)1 & not 0 != 1", c_expression("~(uint32_t)1 & !0 != 1"));
+        assert_eq!("(not x) || not y", c_expression("(!x) || !y"));
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+/// An integer type of the word-size model: the number of bits and whether values are signed.
+/// All values are stored as `i64`; `normalize` brings a value into the range of the type.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IntType {
+    pub width: u32,
+    pub signed: bool,
+}
+
+impl IntType {
+    pub const I64: IntType = IntType { width: 64, signed: true };
+
+    /// Creates a type of `width` bits, which has to be one of 8, 16, 32 or 64.
+    pub fn new(width: u32, signed: bool) -> Option<Self> {
+        matches!(width, 8 | 16 | 32 | 64).then_some(Self { width, signed })
+    }
+
+    /// returns the type with the Rust name `name`, e.g. `u8` or `i32`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let signed = match name.chars().next()? {
+            'i' => true,
+            'u' => false,
+            _ => return None,
+        };
+        let int_type = Self::new(name[1..].parse().ok()?, signed)?;
+        (int_type.to_string() == name).then_some(int_type)
+    }
+
+    /// returns a mask with the lowest `width` bits set.
+    pub fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    /// truncates `value` to `width` bits, then sign-extends it for signed and zero-extends it for
+    /// unsigned types.
+    /// # Example
+    /// ```
+    /// assert_eq!(IntType { width: 8, signed: true }.normalize(0xff), -1);
+    /// assert_eq!(IntType { width: 8, signed: false }.normalize(-1), 0xff);
+    /// ```
+    pub fn normalize(&self, value: i64) -> i64 {
+        let shift = 64 - self.width;
+        if self.signed {
+            (value << shift) >> shift
+        } else {
+            ((value as u64) << shift >> shift) as i64
+        }
+    }
+
+    /// `true` if `value` is in the range of this type without truncation.
+    pub fn contains(&self, value: i128) -> bool {
+        let (min, max) = if self.signed {
+            (-(1_i128 << (self.width - 1)), (1_i128 << (self.width - 1)) - 1)
+        } else {
+            (0, (1_i128 << self.width) - 1)
+        };
+        (min..=max).contains(&value)
+    }
+
+    /// returns the value with the full precision of this type, which matters for unsigned 64 bit.
+    pub fn to_i128(self, value: i64) -> i128 {
+        if self.signed {
+            value as i128
+        } else {
+            (value as u64 & self.mask()) as i128
+        }
+    }
+}
+
+impl Default for IntType {
+    fn default() -> Self {
+        Self::I64
+    }
+}
+
+impl fmt::Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { 'i' } else { 'u' }, self.width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(IntType { width: 8, signed: false }), IntType::from_name("u8"));
+        assert_eq!(Some(IntType::I64), IntType::from_name("i64"));
+        assert_eq!(None, IntType::from_name("i08"));
+        assert_eq!(None, IntType::from_name("u128"));
+        assert_eq!(None, IntType::from_name("x"));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let u8 = IntType { width: 8, signed: false };
+        let i8 = IntType { width: 8, signed: true };
+        assert_eq!(0xff, u8.normalize(-1));
+        assert_eq!(0x34, u8.normalize(0x1234));
+        assert_eq!(-1, i8.normalize(0xff));
+        assert_eq!(127, i8.normalize(127));
+        assert_eq!(-1, IntType::I64.normalize(-1));
+        assert_eq!(-1, IntType { width: 64, signed: false }.normalize(-1));
+    }
+
+    #[test]
+    fn test_contains() {
+        let i8 = IntType { width: 8, signed: true };
+        assert!(i8.contains(-128));
+        assert!(!i8.contains(128));
+        assert!(IntType { width: 64, signed: false }.contains(u64::MAX as i128));
+        assert!(!IntType::I64.contains(u64::MAX as i128));
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::format::{self, FormatOptions, Radix};
+use crate::vm::VM;
+
+/// returns `text` as JSON string literal, including the quotes.
+pub fn string(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+    output.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+    output
+}
+
+fn span(span: &Range<usize>) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", span.start, span.end)
+}
+
+/// renders the evaluation of `input` as a single line JSON object, so a stream of evaluations can be
+/// read line by line. `result` has to be the result of the last `vm.run`.
+/// # Example
+/// ```
+/// {"input":"0xff","ok":true,"value":255,"dec":"255","hex":"0xff","oct":"0o377","bin":"0b11111111",
+///  "type":{"width":64,"signed":true},"flags":{"zero":false,"sign":false,"overflow":false},"warnings":[]}
+/// {"input":"5 % 0","ok":false,"error":{"kind":"division_by_zero","message":"division by zero","span":{"start":2,"end":3}},
+///  "type":{"width":64,"signed":true}}
+/// ```
+pub fn evaluation(input: &str, result: &Result<i64, Error>, vm: &VM) -> String {
+    let int_type = vm.int_type();
+    let type_info = format!("\"type\":{{\"width\":{},\"signed\":{}}}", int_type.width, int_type.signed);
+    match result {
+        Ok(value) => {
+            let options = FormatOptions::default();
+            let flags = vm.flags();
+            let warnings: Vec<_> = vm
+                .warnings()
+                .iter()
+                .map(|warning| format!("{{\"message\":{},\"span\":{}}}", string(&warning.message), span(&warning.span)))
+                .collect();
+            format!(
+                "{{\"input\":{},\"ok\":true,\"value\":{},\"dec\":{},\"hex\":{},\"oct\":{},\"bin\":{},{},\"flags\":{{\"zero\":{},\"sign\":{},\"overflow\":{}}},\"warnings\":[{}]}}",
+                string(input),
+                int_type.to_i128(*value),
+                string(&format::format(*value, Radix::Dec, &options, int_type)),
+                string(&format::format(*value, Radix::Hex, &options, int_type)),
+                string(&format::format(*value, Radix::Oct, &options, int_type)),
+                string(&format::format(*value, Radix::Bin, &options, int_type)),
+                type_info,
+                flags.zero,
+                flags.sign,
+                flags.overflow,
+                warnings.join(","),
+            )
+        }
+        Err(err) => format!(
+            "{{\"input\":{},\"ok\":false,\"error\":{{\"kind\":\"{}\",\"message\":{},\"span\":{}}},{}}}",
+            string(input),
+            err.kind(),
+            string(&err.to_string()),
+            span(&err.span().unwrap_or(input.len()..input.len())),
+            type_info,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::int_type::IntType;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(r#""1 + 1""#, string("1 + 1"));
+        assert_eq!(r#""a\"b\\c\nd\u001b""#, string("a\"b\\c\nd\x1b"));
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let mut vm = VM::new();
+        let result = vm.run("0xff");
+        assert_eq!(
+            concat!(
+                r#"{"input":"0xff","ok":true,"value":255,"dec":"255","hex":"0xff","oct":"0o377","bin":"0b11111111","#,
+                r#""type":{"width":64,"signed":true},"flags":{"zero":false,"sign":false,"overflow":false},"warnings":[]}"#
+            ),
+            evaluation("0xff", &result, &vm)
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut vm = VM::new();
+        vm.set_int_type(IntType::new(8, false).unwrap());
+        let result = vm.run("0xff + 1");
+        assert!(evaluation("0xff + 1", &result, &vm).ends_with(
+            r#""flags":{"zero":true,"sign":false,"overflow":true},"warnings":[{"message":"result of `+` does not fit into u8 and wrapped around","span":{"start":5,"end":6}}]}"#
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut vm = VM::new();
+        let result = vm.run("5 % 0");
+        assert_eq!(
+            r#"{"input":"5 % 0","ok":false,"error":{"kind":"division_by_zero","message":"division by zero","span":{"start":2,"end":3}},"type":{"width":64,"signed":true}}"#,
+            evaluation("5 % 0", &result, &vm)
+        );
+        let result = vm.run("(1");
+        assert!(evaluation("(1", &result, &vm).contains(r#""kind":"unexpected_eof","message":"unexpected end of input","span":{"start":2,"end":2}"#));
+    }
+}
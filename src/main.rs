@@ -1,42 +1,361 @@
+mod cli;
+mod clipboard;
+mod dump;
+mod command;
+mod error;
+mod format;
+mod functions;
+mod header;
+mod int_type;
+mod json;
 mod scanner;
+mod svd;
+mod script;
 mod parser;
+mod registers;
+mod tui;
 mod vm;
+mod watch;
+mod xml;
 mod editor;
+mod elf;
 
+use std::io::IsTerminal;
+
+use ariadne::{Label, Report, ReportKind, Source};
+use cli::{Options, OutputFormat};
+use clipboard::Clipboard;
+use command::{Command, CopyTarget};
+use error::Error;
+use format::{FormatOptions, Radix};
+use registers::Register;
 use vm::VM;
 use editor::*;
 
 fn main() {
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, cli::USAGE);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
     let mut vm = VM::new();
-    let args: Vec<_> = std::env::args().collect();
-    if let Some(file_name) = args.get(1) {
-        let file_content = std::fs::read_to_string(file_name).unwrap();
-        let result = vm.run(&file_content);
-        println!("result: {:?}", result);
+    vm.set_int_type(options.int_type);
+    vm.set_shift_mode(options.shift_mode);
+    vm.set_page_size(options.page_size);
+
+    let exit_code = if options.test {
+        run_tests(&vm, &options)
+    } else if options.tui {
+        match tui::run(&mut vm) {
+            Ok(()) => cli::EXIT_SUCCESS,
+            Err(err) => {
+                eprintln!("{}", err);
+                cli::EXIT_IO_ERROR
+            }
+        }
+    } else if let Some(file_name) = options.watch.clone() {
+        match watch::run(&file_name, &vm) {
+            Ok(()) => cli::EXIT_SUCCESS,
+            Err(err) => {
+                eprintln!("{}", err);
+                cli::EXIT_IO_ERROR
+            }
+        }
+    } else if let Some(file_name) = options.annotate.clone() {
+        annotate(&mut vm, &file_name, options.stdout)
+    } else if !options.expressions.is_empty() {
+        let expressions = std::mem::take(&mut options.expressions);
+        evaluate_all(&mut vm, &options, expressions.iter().map(String::as_str))
+    } else if let Some(argument) = options.argument.clone() {
+        //the positional argument is a script file if it exists, otherwise an expression
+        if std::path::Path::new(&argument).is_file() {
+            match std::fs::read_to_string(&argument) {
+                Ok(source) => script::run(&mut vm, &argument, &source, &options),
+                Err(err) => {
+                    eprintln!("could not read {}: {}", argument, err);
+                    cli::EXIT_IO_ERROR
+                }
+            }
+        } else {
+            evaluate_all(&mut vm, &options, [argument.as_str()])
+        }
+    } else {
+        repl(&mut vm, &mut options)
+    };
+    std::process::exit(exit_code);
+}
+
+/// evaluates every expression and prints the results, returns the exit code.
+fn evaluate_all<'a>(vm: &mut VM, options: &Options, expressions: impl IntoIterator<Item = &'a str>) -> i32 {
+    let mut exit_code = cli::EXIT_SUCCESS;
+    for expression in expressions {
+        if !evaluate(vm, options, expression) {
+            exit_code = cli::EXIT_EVALUATION_ERROR;
+        }
+    }
+    exit_code
+}
+
+/// evaluates `expression` and prints the result or the error, returns `false` on errors.
+fn evaluate(vm: &mut VM, options: &Options, expression: &str) -> bool {
+    let result = vm.run(expression);
+    let int_type = vm.int_type();
+    match (options.format, &result) {
+        (OutputFormat::Json, _) => println!("{}", json::evaluation(expression, &result, vm)),
+        (OutputFormat::All, Ok(value)) => {
+            let format_options = FormatOptions::default();
+            println!("{}", format::format(*value, Radix::Dec, &format_options, int_type));
+            println!("{}", format::format(*value, Radix::Hex, &format_options, int_type));
+            //a single byte has nothing to swap
+            if int_type.width > 8 {
+                let swapped = functions::swap_bytes(*value, int_type.width);
+                println!("{} (byte-swapped)", format::format(swapped, Radix::Hex, &format_options, int_type));
+            }
+            println!("{}", format::format(*value, Radix::Bin, &format_options, int_type));
+        }
+        (OutputFormat::Radix(radix), Ok(value)) => println!("{}", format::format(*value, radix, &FormatOptions::default(), int_type)),
+        (_, Err(err)) => report_error(expression, err),
+    }
+    if let (Ok(value), false) = (&result, options.format == OutputFormat::Json) {
+        for warning in vm.warnings() {
+            report(ReportKind::Warning, expression, &warning.message, warning.span.clone());
+        }
+        for mode in &options.output_modes {
+            println!("{}", mode.render(*value, int_type.width, vm.page_size()));
+        }
+    }
+    result.is_ok()
+}
+
+/// annotates every line of the file with its result, either in place or on stdout. Returns the exit code.
+fn annotate(vm: &mut VM, file_name: &str, stdout: bool) -> i32 {
+    let source = match std::fs::read_to_string(file_name) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("could not read {}: {}", file_name, err);
+            return cli::EXIT_IO_ERROR;
+        }
+    };
+    let (annotated, success) = script::annotate(vm, &source);
+    if stdout {
+        print!("{}", annotated);
+    } else if let Err(err) = std::fs::write(file_name, annotated) {
+        eprintln!("could not write {}: {}", file_name, err);
+        return cli::EXIT_IO_ERROR;
+    }
+    if success { cli::EXIT_SUCCESS } else { cli::EXIT_EVALUATION_ERROR }
+}
+
+/// runs every `*.calc` file of the directory given as argument, or the given file, as test and
+/// prints which passed and why the others failed. Every file starts with a copy of `vm`. Returns the exit code.
+fn run_tests(vm: &VM, options: &Options) -> i32 {
+    let path = std::path::Path::new(options.argument.as_deref().unwrap_or("."));
+    let files = if path.is_file() {
+        Ok(vec![path.to_path_buf()])
     } else {
-        let editor = Editor::new();
-        loop {
-            match editor.read_line() {
-                Ok(line) if line == "exit" || line == "quit" => {
-                    break;
+        std::fs::read_dir(path).and_then(|entries| {
+            let mut files = Vec::new();
+            for entry in entries {
+                let file = entry?.path();
+                if file.is_file() && file.extension().is_some_and(|extension| extension == "calc") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+            Ok(files)
+        })
+    };
+    let files = match files {
+        Ok(files) if files.is_empty() => {
+            eprintln!("no *.calc files in {}", path.display());
+            return cli::EXIT_EVALUATION_ERROR;
+        }
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("could not read {}: {}", path.display(), err);
+            return cli::EXIT_IO_ERROR;
+        }
+    };
+    let mut failed = 0;
+    for file in &files {
+        let file_name = file.display().to_string();
+        let diagnostics = match std::fs::read_to_string(file) {
+            Ok(source) => script::test(&mut vm.clone(), &file_name, &source),
+            Err(err) => vec![format!("could not read {}: {}", file_name, err)],
+        };
+        if diagnostics.is_empty() {
+            println!("PASS {}", file_name);
+        } else {
+            failed += 1;
+            println!("FAIL {}", file_name);
+            for diagnostic in diagnostics {
+                println!("    {}", diagnostic);
+            }
+        }
+    }
+    println!("{} passed, {} failed", files.len() - failed, failed);
+    if failed == 0 { cli::EXIT_SUCCESS } else { cli::EXIT_EVALUATION_ERROR }
+}
+
+/// reads and evaluates lines until `exit` or the end of input, returns the exit code.
+/// Without a terminal on stdin no prompt is shown, so the REPL also works as a filter in a pipe.
+fn repl(vm: &mut VM, options: &mut Options) -> i32 {
+    let interactive = std::io::stdin().is_terminal();
+    let editor = if interactive { Editor::new() } else { Editor::without_prompt() };
+    let clipboard = Clipboard::detect();
+    let mut last_expression = None;
+    let mut registers: Vec<Register> = Vec::new();
+    let mut exit_code = cli::EXIT_SUCCESS;
+    loop {
+        let line = match editor.read_line() {
+            Ok(line) => line,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                return cli::EXIT_IO_ERROR;
+            }
+        };
+        match Command::parse(&line) {
+            Ok(Command::Exit) => break,
+            Ok(Command::Copy(target, format_options)) => {
+                let text = match target {
+                    CopyTarget::Value(radix) => vm
+                        .previous_result()
+                        .map(|result| format::format(result, radix, &format_options, vm.int_type())),
+                    CopyTarget::Expression => last_expression.clone(),
+                };
+                match text {
+                    Some(text) => {
+                        if let Err(err) = clipboard.copy(&text) {
+                            eprintln!("could not copy to clipboard: {}", err);
+                        }
+                    }
+                    None => eprintln!("nothing to copy yet"),
+                }
+            }
+            Ok(Command::Show(mode)) => {
+                if !options.output_modes.contains(&mode) {
+                    options.output_modes.push(mode);
                 }
-                Ok(line) if line == "copy bin" => {
-                    //TODO vm.result.as_bin() to clipboard;
+                if let Some(result) = vm.previous_result() {
+                    println!("{}", mode.render(result, vm.int_type().width, vm.page_size()));
                 }
-                Ok(line) if line == "copy dec" => {
-                    //TODO vm.result.as_dec() to clipboard;
+            }
+            Ok(Command::Hide(mode)) => options.output_modes.retain(|enabled| *enabled != mode),
+            Ok(Command::Shift(mode)) => vm.set_shift_mode(mode),
+            Ok(Command::PageSize(size)) => vm.set_page_size(size),
+            Ok(Command::Load(file_name)) => load(vm, &mut registers, file_name),
+            Ok(Command::Include(file_name)) => match header::load(vm, file_name) {
+                Ok(inclusion) => {
+                    println!("defined {} constants from {}", inclusion.defined.len(), file_name);
+                    for (constant, reason) in inclusion.failed {
+                        eprintln!("{}:{}: could not evaluate `{}`: {}", file_name, constant.line, constant.name, reason);
+                    }
+                }
+                Err(msg) => eprintln!("{}", msg),
+            },
+            Ok(Command::LoadElf(file_name)) => match elf::load(file_name) {
+                Ok(symbols) => {
+                    println!("loaded {} symbols from {}", symbols.len(), file_name);
+                    vm.add_symbols(symbols);
                 }
-                Ok(line) if line == "copy hex" => {
-                    //TODO vm.result.as_hex() to clipboard;
+                Err(msg) => eprintln!("{}", msg),
+            },
+            Ok(Command::Lookup(expression)) => match vm.query(expression) {
+                Ok(address) => match vm.symbol_at(address) {
+                    Some((symbol, 0)) => println!("{}", symbol.name),
+                    Some((symbol, offset)) => println!("{}+{:#x}", symbol.name, offset),
+                    None => eprintln!("no symbol at {}", format::summary(address, vm.int_type())),
+                },
+                Err(err) => report_error(expression, &err),
+            },
+            Ok(Command::Hexdump(file_name, offset, len)) => match (vm.query(offset), vm.query(len)) {
+                (Ok(offset), Ok(len)) => {
+                    let int_type = vm.int_type();
+                    let (offset, len) = (offset as u64 & int_type.mask(), int_type.to_i128(len));
+                    match usize::try_from(len).map_err(|_| format!("invalid length {}", len)).and_then(|len| dump::hexdump(file_name, offset, len)) {
+                        Ok(dump) => println!("{}", dump),
+                        Err(msg) => eprintln!("{}", msg),
+                    }
                 }
-                Ok(line) => {
-                    let result = vm.run(&line);
-                    println!("{}", &result);
-                    println!("0x{:x}", &result);
-                    println!("0b{:b}", &result);
+                (Err(err), _) => report_error(offset, &err),
+                (_, Err(err)) => report_error(len, &err),
+            },
+            Ok(Command::Decode(name, expression)) => match registers.iter().find(|register| register.name == name) {
+                Some(register) => match vm.query(expression) {
+                    Ok(value) => println!("{}", register.decode(value)),
+                    Err(err) => report_error(expression, &err),
+                },
+                None => eprintln!("unknown register `{}`, load its definition with `load FILE`", name),
+            },
+            Ok(Command::Encode(name, fields)) => match registers.iter().find(|register| register.name == name) {
+                Some(register) => match register.encode(&fields, |expression| vm.query(expression).map_err(|err| err.to_string())) {
+                    Ok(value) => {
+                        vm.set_previous_result(vm.int_type().normalize(value));
+                        println!("{}", register.decode(value));
+                    }
+                    Err(msg) => eprintln!("{}", msg),
+                },
+                None => eprintln!("unknown register `{}`, load its definition with `load FILE`", name),
+            },
+            Ok(Command::Evaluate("")) => {}
+            Ok(Command::Evaluate(expression)) => {
+                if evaluate(vm, options, expression) {
+                    last_expression = Some(expression.to_string());
+                } else if !interactive {
+                    exit_code = cli::EXIT_EVALUATION_ERROR;
+                }
+            }
+            Err(msg) => eprintln!("{}", msg),
+        }
+    }
+    exit_code
+}
+
+/// loads the register definitions in `file_name`, an SVD file or a register definition file, into `registers`.
+/// The addresses of registers and peripherals become variables.
+fn load(vm: &mut VM, registers: &mut Vec<Register>, file_name: &str) {
+    let loaded = if file_name.ends_with(".svd") {
+        svd::load(file_name).map(|device| {
+            println!("loaded {} registers of {} peripherals from {}", device.registers.len(), device.peripherals.len(), file_name);
+            for (name, base) in device.peripherals {
+                vm.set_variable(&name, base);
+            }
+            device.registers
+        })
+    } else {
+        registers::load(file_name).inspect(|loaded| println!("loaded {} registers from {}", loaded.len(), file_name))
+    };
+    match loaded {
+        Ok(loaded) => {
+            for register in loaded {
+                if let Some(address) = register.address {
+                    vm.set_variable(&register.name, address);
                 }
-                err => panic!("{:?}", err),
+                registers.retain(|known| known.name != register.name);
+                registers.push(register);
             }
         }
+        Err(msg) => eprintln!("{}", msg),
     }
 }
+
+/// prints `err` to stderr, pointing at the offending part of `input`.
+fn report_error(input: &str, err: &Error) {
+    report(ReportKind::Error, input, &err.to_string(), err.span().unwrap_or(input.len()..input.len()));
+}
+
+fn report(kind: ReportKind, input: &str, message: &str, span: std::ops::Range<usize>) {
+    Report::build(kind, (), span.start)
+        .with_message(message)
+        .with_label(Label::new(span).with_message(message))
+        .finish()
+        .eprint(Source::from(input))
+        .unwrap();
+}
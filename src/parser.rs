@@ -1,8 +1,13 @@
-use std::sync::mpsc::{self, channel, Sender, Receiver};
-use std::thread;
-use std::fmt;
+use std::ops::Range;
+
+use crate::dump;
+use crate::error::Error;
+use crate::int_type::IntType;
 use crate::scanner::{Scanner, Token};
 
+/// A token of the parsed expression together with its byte range in the input.
+pub type Spanned = (Token, Range<usize>);
+
 #[derive(Clone)]
 pub struct Parser<'a> {
     buffer: &'a str,
@@ -15,36 +20,80 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Token> {
-        let input = self.buffer.clone();
-        let mut scanner = Scanner::new(&input);
-        self.parser_worker(&mut scanner, 0)
+    /// parses the input into a list of tokens in postfix order.
+    pub fn parse(&mut self) -> Result<Vec<Token>, Error> {
+        Ok(self.parse_spanned()?.into_iter().map(|(token, _)| token).collect())
     }
 
-    fn parser_worker(&mut self, scanner: &mut Scanner, min_bp: u8) -> Vec<Token> {
-        let next = scanner.next();
+    /// like `parse`, but keeps the position of every token, e.g. to point at it in error messages.
+    pub fn parse_spanned(&mut self) -> Result<Vec<Spanned>, Error> {
+        let mut scanner = Scanner::new(self.buffer);
+        //an assignment `name = expression` stores the value of the expression after evaluating it
+        let mut lookahead = scanner.clone();
+        let assignment = match (lookahead.next_spanned(), lookahead.next()) {
+            ((Token::Identifier(name), span), Token::Assign) => {
+                scanner = lookahead;
+                Some((Token::Store(name), span))
+            }
+            _ => None,
+        };
+        let mut expression = self.parser_worker(&mut scanner, 0)?;
+        expression.extend(assignment);
+        //the whole input has to be consumed, otherwise there are leftovers like a closing paren
+        match scanner.next_spanned() {
+            (Token::Eof, _) => Ok(expression),
+            (token, span) => Err(self.unexpected(token, span)),
+        }
+    }
+
+    fn parser_worker(&mut self, scanner: &mut Scanner, min_bp: u8) -> Result<Vec<Spanned>, Error> {
+        let (next, span) = scanner.next_spanned();
         let mut lhs = match next {
-            //next should be a number or a left paren
-            Token::DecimalNumber(_) | Token::BinaryNumber(_) | Token::HexNumber(_) | Token::PreviousResult => vec![next],
-            Token::LeftParen => {
-                let lhs = self.parser_worker(scanner, 0);
-                assert_eq!(scanner.next(), Token::RightParen);
-                lhs
+            //next should be a number, a variable, a function call or a left paren
+            Token::Identifier(name) if name == "sizeof" && scanner.peek() == Token::LeftParen => match self.size_of(scanner, span.start) {
+                Some(size_of) => size_of,
+                None => self.call(scanner, name, span)?,
+            },
+            Token::Identifier(name) if scanner.peek() == Token::LeftParen => match dump::read_function(&name) {
+                Some((size, big_endian)) => self.read_file(scanner, size, big_endian, span)?,
+                None => self.call(scanner, name, span)?,
+            },
+            //`&` in front of a name is the address of a symbol, like in C
+            Token::And => match scanner.next_spanned() {
+                (Token::Identifier(name), name_span) => vec![(Token::AddressOf(name), span.start..name_span.end)],
+                (token, span) => return Err(self.unexpected(token, span)),
             },
-            Token::TwosComplement | Token::Bang | Token::Minus => {
+            Token::DecimalNumber(_) | Token::BinaryNumber(_) | Token::HexNumber(_) | Token::TypedNumber(..) | Token::PreviousResult | Token::Identifier(_) => {
+                vec![(next, span)]
+            }
+            Token::LeftParen => match self.cast(scanner, span.start)? {
+                Some(cast) => cast,
+                None => {
+                    let lhs = self.parser_worker(scanner, 0)?;
+                    match scanner.next_spanned() {
+                        (Token::RightParen, _) => lhs,
+                        (token, span) => return Err(self.unexpected(token, span)),
+                    }
+                }
+            },
+            Token::TwosComplement | Token::Bang | Token::Minus | Token::Not => {
                 let ((), r_bp) = self.prefix_binding_power(&next);
-                let mut rhs = self.parser_worker(scanner, r_bp);
-                rhs.append(&mut vec![next.clone()]);
+                let mut rhs = self.parser_worker(scanner, r_bp)?;
+                let op = if next == Token::Minus { Token::Negate } else { next };
+                rhs.push((op, span));
                 rhs
             },
-            _ => panic!("bad token {:?}", &next),
+            token => return Err(self.unexpected(token, span)),
         };
 
         loop {
             let op = match scanner.peek() {
-                eof if eof == Token::Eof => break,
+                Token::Eof => break,
                 operator if operator.is_operator() => operator,
-                err => panic!("bad token >>> {:?}", err),
+                _ => {
+                    let (token, span) = scanner.next_spanned();
+                    return Err(self.unexpected(token, span));
+                }
             };
             //now compute the binding power of the just fetched operator
             if let Some((l_bp, r_bp)) = self.infix_binding_power(&op) {
@@ -52,33 +101,173 @@ impl<'a> Parser<'a> {
                 if l_bp < min_bp {
                     break;
                 }
-                scanner.next(); //eat the previous looked at operator (this is safe, because op breaks out of the loop if op.peek() == eof)
+                //eat the previous looked at operator (this is safe, because op breaks out of the loop if op.peek() == eof)
+                let (_, op_span) = scanner.next_spanned();
 
-                let rhs = self.parser_worker(scanner, r_bp);
-                lhs.append(&mut rhs.clone());
-                lhs.append(&mut vec![op.clone()]);
+                match op {
+                    //`x as u8` casts the lhs
+                    Token::As => match scanner.next_spanned() {
+                        (Token::Identifier(name), type_span) if IntType::from_name(&name).is_some() => {
+                            lhs.push((Token::Cast(IntType::from_name(&name).unwrap()), op_span.start..type_span.end));
+                        }
+                        (token, span) => return Err(self.unexpected(token, span)),
+                    },
+                    //`4 KiB` multiplies with the size of the unit
+                    Token::Unit(_) => lhs.push((op, op_span)),
+                    //`x to KiB` and `x to pages` convert a number of bytes
+                    Token::To => match scanner.next_spanned() {
+                        (Token::Unit(size), unit_span) => lhs.push((Token::ConvertTo(size), op_span.start..unit_span.end)),
+                        (Token::Identifier(name), unit_span) if name == "pages" || name == "page" => {
+                            lhs.push((Token::ConvertToPages, op_span.start..unit_span.end));
+                        }
+                        (token, span) => return Err(self.unexpected(token, span)),
+                    },
+                    //the rhs is skipped if the lhs already decides the result
+                    Token::LogicalAnd | Token::LogicalOr => {
+                        let mut rhs = self.parser_worker(scanner, r_bp)?;
+                        let skip = rhs.len() + 1;
+                        let jump = if op == Token::LogicalAnd { Token::AndThen(skip) } else { Token::OrElse(skip) };
+                        lhs.push((jump, op_span.clone()));
+                        lhs.append(&mut rhs);
+                        lhs.push((Token::IsTrue, op_span));
+                    }
+                    //`condition ? then : else` evaluates only one of the branches
+                    Token::Question => {
+                        let mut then = self.parser_worker(scanner, 0)?;
+                        let colon_span = match scanner.next_spanned() {
+                            (Token::Colon, span) => span,
+                            (token, span) => return Err(self.unexpected(token, span)),
+                        };
+                        let mut otherwise = self.parser_worker(scanner, r_bp)?;
+                        lhs.push((Token::JumpIfZero(then.len() + 1), op_span));
+                        lhs.append(&mut then);
+                        lhs.push((Token::Jump(otherwise.len()), colon_span));
+                        lhs.append(&mut otherwise);
+                    }
+                    _ => {
+                        let mut rhs = self.parser_worker(scanner, r_bp)?;
+                        lhs.append(&mut rhs);
+                        lhs.push((op, op_span));
+                    }
+                }
                 continue;
             }
             break;
         }
-        lhs
+        Ok(lhs)
+    }
+
+    ///parses a cast like `(u8)x` after its opening paren at `start`, returns `None` if the paren does not start a cast.
+    fn cast(&mut self, scanner: &mut Scanner, start: usize) -> Result<Option<Vec<Spanned>>, Error> {
+        let mut lookahead = scanner.clone();
+        let int_type = match (lookahead.next(), lookahead.next_spanned()) {
+            (Token::Identifier(name), (Token::RightParen, paren_span)) => match IntType::from_name(&name) {
+                Some(int_type) => (int_type, start..paren_span.end),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        *scanner = lookahead;
+        let (int_type, span) = int_type;
+        let cast = Token::Cast(int_type);
+        let ((), r_bp) = self.prefix_binding_power(&cast);
+        let mut operand = self.parser_worker(scanner, r_bp)?;
+        operand.push((cast, span));
+        Ok(Some(operand))
+    }
+
+    ///parses `sizeof(name)` after `sizeof` at `start`, which is the size of a symbol or of a type like `u32`.
+    ///Returns `None` if the parens contain something else, which is parsed as a call.
+    fn size_of(&mut self, scanner: &mut Scanner, start: usize) -> Option<Vec<Spanned>> {
+        let mut lookahead = scanner.clone();
+        let (name, span) = match (lookahead.next(), lookahead.next(), lookahead.next_spanned()) {
+            (Token::LeftParen, Token::Identifier(name), (Token::RightParen, paren_span)) => (name, start..paren_span.end),
+            _ => return None,
+        };
+        *scanner = lookahead;
+        Some(match IntType::from_name(&name) {
+            Some(int_type) => vec![(Token::DecimalNumber(int_type.width as i64 / 8), span)],
+            None => vec![(Token::SizeOf(name), span)],
+        })
+    }
+
+    ///parses the arguments of a call of the function `name` up to the closing paren.
+    ///The arguments are followed by `Token::Call`.
+    fn call(&mut self, scanner: &mut Scanner, name: String, span: Range<usize>) -> Result<Vec<Spanned>, Error> {
+        scanner.next();
+        let mut output = Vec::new();
+        let mut count = 0;
+        if scanner.peek() == Token::RightParen {
+            scanner.next();
+        } else {
+            loop {
+                output.append(&mut self.parser_worker(scanner, 0)?);
+                count += 1;
+                match scanner.next_spanned() {
+                    (Token::Comma, _) => continue,
+                    (Token::RightParen, _) => break,
+                    (token, span) => return Err(self.unexpected(token, span)),
+                }
+            }
+        }
+        output.push((Token::Call(name, count), span));
+        Ok(output)
+    }
+
+    ///parses the file name and the offset of a read function like `read_u32("dump.bin", 0x40)` up to the closing paren.
+    ///The offset is followed by `Token::ReadFile`.
+    fn read_file(&mut self, scanner: &mut Scanner, size: usize, big_endian: bool, span: Range<usize>) -> Result<Vec<Spanned>, Error> {
+        scanner.next();
+        let file_name = match scanner.next_spanned() {
+            (Token::Text(file_name), _) => file_name,
+            (token, span) => return Err(self.unexpected(token, span)),
+        };
+        match scanner.next_spanned() {
+            (Token::Comma, _) => {}
+            (token, span) => return Err(self.unexpected(token, span)),
+        }
+        let mut output = self.parser_worker(scanner, 0)?;
+        match scanner.next_spanned() {
+            (Token::RightParen, _) => {}
+            (token, span) => return Err(self.unexpected(token, span)),
+        }
+        output.push((Token::ReadFile(file_name, size, big_endian), span));
+        Ok(output)
+    }
+
+    ///turns the `token` found at `span` into the matching error.
+    fn unexpected(&self, token: Token, span: Range<usize>) -> Error {
+        match token {
+            Token::Eof => Error::UnexpectedEof,
+            Token::Error(start, end, lexeme) => Error::InvalidToken(start..end, lexeme),
+            _ => Error::UnexpectedToken(span.clone(), self.buffer[span].to_string()),
+        }
     }
 
     ///returns the precedence of the given `op`.
     ///# Example
     /// ```
-    /// assert_eq!(infix_binding_power(Token::new(Token::Plus, 0, 1), (11, 12)));
+    /// assert_eq!(infix_binding_power(Token::new(Token::Plus, 0, 1), (21, 22)));
     /// ```
+    ///Unlike C, the bitwise operators bind tighter than the comparisons, so `x & 0x80 != 0` tests the bit.
     fn infix_binding_power(&self, op: &Token) -> Option<(u8, u8)> {
         let res = match &op {
-            Token::Modulo => (13, 14),
-            Token::Mult => (13, 14),
-            Token::Plus | Token::Minus => (11, 12), //highest precedence
-            Token::ShiftRight | Token::ShiftLeft => (9, 10),
-            Token::And => (7, 8),
-            Token::Xor => (5, 6),
-            Token::Nor => (3, 4),
-            Token::Or => (1, 2),
+            Token::As | Token::Unit(_) => (27, 28), //highest precedence, only prefix operators bind tighter
+            Token::Power => (26, 25), //right associative, `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+            Token::Modulo => (23, 24),
+            Token::Mult => (23, 24),
+            Token::Plus | Token::Minus => (21, 22),
+            Token::ShiftRight | Token::ShiftLeft | Token::LogicalShiftRight | Token::RotateLeft | Token::RotateRight => (19, 20),
+            Token::And => (17, 18),
+            Token::Xor => (15, 16),
+            Token::Nor => (13, 14),
+            Token::Or => (11, 12),
+            Token::Less | Token::LessEqual | Token::Greater | Token::GreaterEqual => (9, 10),
+            Token::Equal | Token::NotEqual => (7, 8),
+            Token::LogicalAnd => (5, 6),
+            Token::LogicalOr => (3, 4),
+            Token::To => (3, 4), //applies to everything up to a conditional, `4 MiB + 1 to pages` converts the sum
+            Token::Question => (2, 1), //right associative, `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
             _ => return None,
         };
         Some(res)
@@ -86,7 +275,7 @@ impl<'a> Parser<'a> {
 
     fn prefix_binding_power(&self, op: &Token) -> ((), u8) {
         match op {
-            Token::Minus | Token::Bang | Token::TwosComplement => ((), 255),
+            Token::Minus | Token::Bang | Token::TwosComplement | Token::Not | Token::Cast(_) => ((), 255),
             _ => panic!("bad token {:?}", &op),
         }
     }
@@ -99,7 +288,7 @@ mod tests {
     #[test]
     fn test_bang() {
         let mut p = Parser::new("!5");
-        let result = p.parse();
+        let result = p.parse().unwrap();
         assert_eq!(vec![Token::DecimalNumber(5), Token::Bang], result);
     }
 
@@ -111,4 +300,176 @@ mod tests {
         println!("actual: {:?}", &actual);
     }
 
+    #[test]
+    fn test_spans() {
+        let actual = Parser::new("1 << (22 + 3)").parse_spanned().unwrap();
+        assert_eq!(
+            vec![
+                (Token::DecimalNumber(1), 0..1),
+                (Token::DecimalNumber(22), 6..8),
+                (Token::DecimalNumber(3), 11..12),
+                (Token::Plus, 9..10),
+                (Token::ShiftLeft, 2..4),
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn test_assignment() {
+        let actual = Parser::new("mask = bits & 0xf").parse().unwrap();
+        assert_eq!(
+            vec![Token::Identifier("bits".to_string()), Token::HexNumber(0xf), Token::And, Token::Store("mask".to_string())],
+            actual
+        );
+        assert_eq!(Err(Error::UnexpectedToken(4..5, "=".to_string())), Parser::new("1 + = 2").parse());
+    }
+
+    #[test]
+    fn test_comparison_precedence() {
+        let actual = Parser::new("x & 0x80 != 0").parse().unwrap();
+        assert_eq!(
+            vec![Token::Identifier("x".to_string()), Token::HexNumber(0x80), Token::And, Token::DecimalNumber(0), Token::NotEqual],
+            actual
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let actual = Parser::new("a || b && c").parse().unwrap();
+        let name = |name: &str| Token::Identifier(name.to_string());
+        assert_eq!(
+            vec![name("a"), Token::OrElse(5), name("b"), Token::AndThen(2), name("c"), Token::IsTrue, Token::IsTrue],
+            actual
+        );
+        let actual = Parser::new("a ? b : c ? d : e").parse().unwrap();
+        assert_eq!(
+            vec![
+                name("a"),
+                Token::JumpIfZero(2),
+                name("b"),
+                Token::Jump(5),
+                name("c"),
+                Token::JumpIfZero(2),
+                name("d"),
+                Token::Jump(1),
+                name("e"),
+            ],
+            actual
+        );
+        assert_eq!(Err(Error::UnexpectedEof), Parser::new("a ? b").parse());
+    }
+
+    #[test]
+    fn test_power_and_calls() {
+        let actual = Parser::new("2 * 3 ** 2 ** 2").parse().unwrap();
+        assert_eq!(
+            vec![
+                Token::DecimalNumber(2),
+                Token::DecimalNumber(3),
+                Token::DecimalNumber(2),
+                Token::DecimalNumber(2),
+                Token::Power,
+                Token::Power,
+                Token::Mult,
+            ],
+            actual
+        );
+        let actual = Parser::new("powmod(4, 1 + 2, f()) - 1").parse_spanned().unwrap();
+        assert_eq!(
+            vec![
+                (Token::DecimalNumber(4), 7..8),
+                (Token::DecimalNumber(1), 10..11),
+                (Token::DecimalNumber(2), 14..15),
+                (Token::Plus, 12..13),
+                (Token::Call("f".to_string(), 0), 17..18),
+                (Token::Call("powmod".to_string(), 3), 0..6),
+                (Token::DecimalNumber(1), 24..25),
+                (Token::Minus, 22..23),
+            ],
+            actual
+        );
+        assert_eq!(Err(Error::UnexpectedEof), Parser::new("pow(2,").parse());
+        assert_eq!(Err(Error::UnexpectedToken(6..7, "3".to_string())), Parser::new("pow(2 3)").parse());
+    }
+
+    #[test]
+    fn test_casts() {
+        let u8 = IntType::new(8, false).unwrap();
+        let actual = Parser::new("(u8)x + (x) as i16").parse_spanned().unwrap();
+        assert_eq!(
+            vec![
+                (Token::Identifier("x".to_string()), 4..5),
+                (Token::Cast(u8), 0..4),
+                (Token::Identifier("x".to_string()), 9..10),
+                (Token::Cast(IntType::new(16, true).unwrap()), 12..18),
+                (Token::Plus, 6..7),
+            ],
+            actual
+        );
+        assert_eq!(
+            vec![Token::DecimalNumber(1), Token::Negate, Token::Cast(u8), Token::DecimalNumber(2), Token::Power],
+            Parser::new("(u8)-1 ** 2").parse().unwrap()
+        );
+        assert_eq!(Err(Error::UnexpectedToken(5..9, "bits".to_string())), Parser::new("x as bits").parse());
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(
+            Ok(vec![
+                (Token::AddressOf("main".to_string()), 0..5),
+                (Token::SizeOf("g_buffer".to_string()), 8..24),
+                (Token::Plus, 6..7),
+            ]),
+            Parser::new("&main + sizeof(g_buffer)").parse_spanned()
+        );
+        assert_eq!(Ok(vec![Token::DecimalNumber(4)]), Parser::new("sizeof(u32)").parse());
+        assert_eq!(Ok(vec![Token::DecimalNumber(1), Token::Call("sizeof".to_string(), 1)]), Parser::new("sizeof(1)").parse());
+        assert!(Parser::new("& 1").parse().is_err());
+    }
+
+    #[test]
+    fn test_read_file() {
+        assert_eq!(
+            Ok(vec![Token::HexNumber(0x40), Token::DecimalNumber(4), Token::Plus, Token::ReadFile("dump.bin".to_string(), 4, true)]),
+            Parser::new("read_u32_be(\"dump.bin\", 0x40 + 4)").parse()
+        );
+        assert_eq!(Err(Error::UnexpectedToken(8..9, "0".to_string())), Parser::new("read_u8(0, 1)").parse());
+        assert!(Parser::new("read_u8(\"dump.bin\")").parse().is_err());
+        assert!(Parser::new("\"dump.bin\"").parse().is_err());
+    }
+
+    #[test]
+    fn test_units() {
+        let actual = Parser::new("4 MiB + 1 to pages").parse_spanned().unwrap();
+        assert_eq!(
+            vec![
+                (Token::DecimalNumber(4), 0..1),
+                (Token::Unit(1 << 20), 2..5),
+                (Token::DecimalNumber(1), 8..9),
+                (Token::Plus, 6..7),
+                (Token::ConvertToPages, 10..18),
+            ],
+            actual
+        );
+        assert_eq!(
+            vec![Token::Identifier("x".to_string()), Token::ConvertTo(1000)],
+            Parser::new("x to kB").parse().unwrap()
+        );
+        assert_eq!(Err(Error::UnexpectedToken(5..10, "bytes".to_string())), Parser::new("x to bytes").parse());
+    }
+
+    #[test]
+    fn test_incomplete() {
+        assert_eq!(Err(Error::UnexpectedEof), Parser::new("(1 + 2").parse());
+        assert_eq!(Err(Error::UnexpectedEof), Parser::new("1 <<").parse());
+        assert_eq!(Err(Error::UnexpectedEof), Parser::new("((1 + 2) and").parse());
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        assert_eq!(Err(Error::UnexpectedToken(6..7, ")".to_string())), Parser::new("1 + 2 )").parse());
+        assert_eq!(Err(Error::UnexpectedToken(2..3, "2".to_string())), Parser::new("1 2").parse());
+    }
 }
@@ -0,0 +1,370 @@
+use crate::format;
+use crate::int_type::IntType;
+
+/// the width of a register without a `width` key
+const DEFAULT_WIDTH: u32 = 32;
+
+/// A register as described in a register definition file, see `parse`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Register {
+    pub name: String,
+    pub address: Option<i64>,
+    pub width: u32,
+    /// sorted from the highest to the lowest bit
+    pub fields: Vec<Field>,
+}
+
+/// A bit field of a register, `high` and `low` are included.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name: String,
+    pub high: u32,
+    pub low: u32,
+    /// the names of enumerated values
+    pub values: Vec<(String, u64)>,
+}
+
+impl Field {
+    /// returns the bits of the field in the register.
+    pub fn mask(&self) -> u64 {
+        (u64::MAX >> (63 - self.high)) & (u64::MAX << self.low)
+    }
+
+    /// extracts the value of the field from the `register` value.
+    pub fn get(&self, register: u64) -> u64 {
+        (register & self.mask()) >> self.low
+    }
+
+    fn width(&self) -> u32 {
+        self.high - self.low + 1
+    }
+
+    fn bits(&self) -> String {
+        if self.high == self.low {
+            self.high.to_string()
+        } else {
+            format!("{}:{}", self.high, self.low)
+        }
+    }
+
+    fn value_name(&self, value: u64) -> Option<&str> {
+        self.values.iter().find(|(_, known)| *known == value).map(|(name, _)| name.as_str())
+    }
+}
+
+impl Register {
+    fn int_type(&self) -> IntType {
+        IntType::new(self.width, false).unwrap()
+    }
+
+    /// lists every field of `value` with its bits, value and the name of the value if it is enumerated,
+    /// followed by the bits that are set outside of the fields.
+    /// # Example
+    /// ```text
+    /// CR1 = 0x200c (8204)
+    ///   UE  13   0b1   0x1 (1)
+    ///   PS  9    0b0   0x0 (0)  even
+    /// ```
+    pub fn decode(&self, value: i64) -> String {
+        let int_type = self.int_type();
+        let value = value as u64 & int_type.mask();
+        let name_width = self.fields.iter().map(|field| field.name.len()).max().unwrap_or(0);
+        let bits_width = self.fields.iter().map(|field| field.bits().len()).max().unwrap_or(0);
+        let binary_width = self.fields.iter().map(|field| field.width() as usize + 2).max().unwrap_or(0);
+        let mut lines = vec![format!("{} = {}", self.name, format::summary(value as i64, int_type))];
+        for field in &self.fields {
+            let field_value = field.get(value);
+            //fields can have any width, so they are not formatted as `IntType`
+            let binary = format!("0b{:0width$b}", field_value, width = field.width() as usize);
+            let line = format!(
+                "  {:<name_width$}  {:<bits_width$}  {:<binary_width$}  {:#x} ({})  {}",
+                field.name,
+                field.bits(),
+                binary,
+                field_value,
+                field_value,
+                field.value_name(field_value).unwrap_or_default(),
+            );
+            lines.push(line.trim_end().to_string());
+        }
+        let reserved = value & !self.fields.iter().fold(0, |mask, field| mask | field.mask());
+        if reserved != 0 {
+            lines.push(format!("  bits outside of the fields are set: {:#x}", reserved));
+        }
+        lines.join("\n")
+    }
+
+    /// builds a register value from `assignments` of field names to values, other fields are 0.
+    /// A value is either the name of an enumerated value of the field or an expression, which is evaluated with `evaluate`.
+    pub fn encode(&self, assignments: &[(&str, &str)], mut evaluate: impl FnMut(&str) -> Result<i64, String>) -> Result<i64, String> {
+        let mut register = 0;
+        for (name, text) in assignments {
+            let field = self
+                .fields
+                .iter()
+                .find(|field| field.name == *name)
+                .ok_or(format!("register `{}` has no field `{}`", self.name, name))?;
+            let value = match field.values.iter().find(|(value_name, _)| value_name == text) {
+                Some((_, value)) => *value,
+                None => evaluate(text)? as u64,
+            };
+            if value > field.mask() >> field.low {
+                return Err(format!("value {:#x} does not fit into the field `{}` (bits {})", value, name, field.bits()));
+            }
+            register = register & !field.mask() | value << field.low;
+        }
+        Ok(register as i64)
+    }
+}
+
+/// reads the register definitions from the file `file_name`, see `parse`.
+pub fn load(file_name: &str) -> Result<Vec<Register>, String> {
+    let source = std::fs::read_to_string(file_name).map_err(|err| format!("could not read {}: {}", file_name, err))?;
+    parse(&source).map_err(|err| format!("{}:{}", file_name, err))
+}
+
+/// A value in a register definition file.
+#[derive(Debug, PartialEq, Clone)]
+enum Value {
+    Integer(i64),
+    String(String),
+    Table(Vec<(String, Value)>),
+}
+
+/// parses register definitions, written in a subset of TOML: a table `[REG]` per register with the optional keys
+/// `address` and `width` (default: 32 bits) and a table `[REG.FIELD]` per field with the key `bits`, either a single
+/// bit number or a range `"high:low"`, and the optional inline table `values` that names enumerated values:
+/// ```toml
+/// [CR1]
+/// address = 0x4001100c
+///
+/// [CR1.PS]
+/// bits = 9
+/// values = { even = 0, odd = 1 }
+/// ```
+/// Errors start with the line they refer to, e.g. `3: expected `=``.
+pub fn parse(source: &str) -> Result<Vec<Register>, String> {
+    let mut registers: Vec<Register> = Vec::new();
+    //the register and the field of the last table, the following keys belong to it
+    let mut current: Option<(usize, Option<usize>)> = None;
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{}: {}", number + 1, message);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.strip_suffix(']').ok_or(error("expected `]`".to_string()))?;
+            let path: Vec<&str> = header.split('.').map(str::trim).collect();
+            if path.iter().any(|name| !is_name(name)) || path.len() > 2 {
+                return Err(error(format!("expected `[REGISTER]` or `[REGISTER.FIELD]`, found `[{}]`", header)));
+            }
+            let index = match registers.iter().position(|register| register.name == path[0]) {
+                Some(index) => index,
+                None => {
+                    registers.push(Register { name: path[0].to_string(), address: None, width: DEFAULT_WIDTH, fields: Vec::new() });
+                    registers.len() - 1
+                }
+            };
+            let fields = &mut registers[index].fields;
+            let field = match path.get(1) {
+                Some(name) if fields.iter().any(|field| field.name == *name) => {
+                    return Err(error(format!("field `{}` is defined twice", name)));
+                }
+                //the bits are set by the `bits` key, a field without it is reported by `validate`
+                Some(name) => {
+                    fields.push(Field { name: name.to_string(), high: u32::MAX, low: 0, values: Vec::new() });
+                    Some(fields.len() - 1)
+                }
+                None => None,
+            };
+            current = Some((index, field));
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or(error("expected `key = value`".to_string()))?;
+        let (key, value) = (key.trim(), parse_value(value.trim()).map_err(error)?);
+        let (register, field) = current.ok_or(error(format!("`{}` is outside of a register", key)))?;
+        let register = &mut registers[register];
+        match (field.map(|field| &mut register.fields[field]), key, value) {
+            (None, "address", Value::Integer(address)) => register.address = Some(address),
+            (None, "width", Value::Integer(width)) => {
+                register.width = u32::try_from(width)
+                    .ok()
+                    .filter(|width| IntType::new(*width, false).is_some())
+                    .ok_or(error(format!("invalid width {}, it has to be 8, 16, 32 or 64", width)))?;
+            }
+            (Some(field), "bits", bits) => {
+                (field.high, field.low) = parse_bits(&bits).ok_or(error("expected a bit number or a range like \"7:4\"".to_string()))?;
+            }
+            (Some(field), "values", Value::Table(values)) => {
+                for (name, value) in values {
+                    match value {
+                        Value::Integer(value) if value >= 0 => field.values.push((name, value as u64)),
+                        _ => return Err(error(format!("the value `{}` has to be a number", name))),
+                    }
+                }
+            }
+            (_, key, _) => return Err(error(format!("unexpected key `{}`", key))),
+        }
+    }
+    for register in &mut registers {
+        validate(register)?;
+        register.fields.sort_by_key(|field| std::cmp::Reverse(field.high));
+    }
+    Ok(registers)
+}
+
+/// checks that the fields have bits, fit into the register and don't overlap.
+fn validate(register: &Register) -> Result<(), String> {
+    let mut used = 0;
+    for field in &register.fields {
+        let name = format!("{}.{}", register.name, field.name);
+        if field.high == u32::MAX {
+            return Err(format!("field `{}` has no bits", name));
+        }
+        if field.high >= register.width {
+            return Err(format!("field `{}` does not fit into {} bits", name, register.width));
+        }
+        if used & field.mask() != 0 {
+            return Err(format!("field `{}` overlaps another field", name));
+        }
+        used |= field.mask();
+        if let Some((value_name, _)) = field.values.iter().find(|(_, value)| *value > field.mask() >> field.low) {
+            return Err(format!("value `{}` does not fit into the field `{}`", value_name, name));
+        }
+    }
+    Ok(())
+}
+
+/// removes the comment starting with `#` outside of a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(string) = text.strip_prefix('"') {
+        return string.strip_suffix('"').map(|string| Value::String(string.to_string())).ok_or("expected `\"`".to_string());
+    }
+    if let Some(table) = text.strip_prefix('{') {
+        let table = table.strip_suffix('}').ok_or("expected `}`".to_string())?;
+        let mut entries = Vec::new();
+        for entry in table.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, value) = entry.split_once('=').ok_or(format!("expected `key = value`, found `{}`", entry))?;
+            entries.push((key.trim().to_string(), parse_value(value.trim())?));
+        }
+        return Ok(Value::Table(entries));
+    }
+    parse_integer(text).map(Value::Integer).ok_or(format!("invalid value `{}`", text))
+}
+
+/// parses a decimal, `0x` hex, `0o` octal or `0b` binary integer with optional `_` separators.
+pub fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let text = text.replace('_', "");
+    let (radix, digits) = match text.get(..2) {
+        Some("0x" | "0X") => (16, &text[2..]),
+        Some("0o" | "0O") => (8, &text[2..]),
+        Some("0b" | "0B") => (2, &text[2..]),
+        _ => (10, text.as_str()),
+    };
+    let value = u64::from_str_radix(digits, radix).ok()? as i64;
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+/// returns the highest and the lowest bit of `bits`, either a bit number or a string `"high:low"`.
+fn parse_bits(bits: &Value) -> Option<(u32, u32)> {
+    let bit = |text: &str| text.trim().parse::<u32>().ok().filter(|bit| *bit < 64);
+    let (high, low) = match bits {
+        Value::Integer(bit) => (u32::try_from(*bit).ok().filter(|bit| *bit < 64)?, u32::try_from(*bit).ok()?),
+        Value::String(range) => match range.split_once(':') {
+            Some((high, low)) => (bit(high)?, bit(low)?),
+            None => (bit(range)?, bit(range)?),
+        },
+        Value::Table(_) => return None,
+    };
+    (high >= low).then_some((high, low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CR1: &str = "
+# USART control register 1
+[CR1]
+address = 0x4001_100c
+
+[CR1.PS]
+bits = 9
+values = { even = 0, odd = 1 }
+
+[CR1.UE]
+bits = \"13\"   # enable
+
+[CR1.M]
+bits = \"15:14\"
+values = { bits8 = 0, bits9 = 1, bits7 = 2 }
+";
+
+    #[test]
+    fn test_parse() {
+        let registers = parse(CR1).unwrap();
+        assert_eq!(1, registers.len());
+        let cr1 = &registers[0];
+        assert_eq!(Some(0x4001100c), cr1.address);
+        assert_eq!(32, cr1.width);
+        let names: Vec<_> = cr1.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(vec!["M", "UE", "PS"], names);
+        assert_eq!(0xc000, cr1.fields[0].mask());
+        assert_eq!(vec![("even".to_string(), 0), ("odd".to_string(), 1)], cr1.fields[2].values);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err("2: expected `key = value`".to_string()), parse("[A]\nbits 3"));
+        assert_eq!(Err("1: `width` is outside of a register".to_string()), parse("width = 8"));
+        assert_eq!(Err("field `A.X` does not fit into 8 bits".to_string()), parse("[A]\nwidth = 8\n[A.X]\nbits = \"9:8\""));
+        assert_eq!(Err("field `A.Y` overlaps another field".to_string()), parse("[A]\n[A.X]\nbits = \"7:4\"\n[A.Y]\nbits = 4"));
+        assert_eq!(Err("field `A.X` has no bits".to_string()), parse("[A.X]"));
+        assert_eq!(Err("2: unexpected key `bits`".to_string()), parse("[A]\nbits = 3"));
+        assert!(parse("[A]\nwidth = 24").is_err());
+        assert_eq!(Err("value `big` does not fit into the field `A.X`".to_string()), parse("[A.X]\nbits = \"1:0\"\nvalues = { big = 4 }"));
+    }
+
+    #[test]
+    fn test_decode() {
+        let cr1 = &parse(CR1).unwrap()[0];
+        let lines: Vec<_> = cr1.decode(0x6200).lines().map(str::to_string).collect();
+        assert_eq!("CR1 = 0x6200 (25088)", lines[0]);
+        assert_eq!("  M   15:14  0b01  0x1 (1)  bits9", lines[1]);
+        assert_eq!("  UE  13     0b1   0x1 (1)", lines[2]);
+        assert_eq!("  PS  9      0b1   0x1 (1)  odd", lines[3]);
+        assert_eq!(4, lines.len());
+        assert_eq!("  bits outside of the fields are set: 0x1", cr1.decode(0x1).lines().last().unwrap());
+    }
+
+    #[test]
+    fn test_encode() {
+        let cr1 = &parse(CR1).unwrap()[0];
+        let evaluate = |text: &str| parse_integer(text).ok_or(format!("invalid value `{}`", text));
+        assert_eq!(Ok(0xa200), cr1.encode(&[("M", "bits7"), ("UE", "1"), ("PS", "odd")], evaluate));
+        assert_eq!(Ok(0x4000), cr1.encode(&[("M", "0x1")], evaluate));
+        assert!(cr1.encode(&[("UE", "2")], evaluate).is_err());
+        assert!(cr1.encode(&[("TE", "1")], evaluate).is_err());
+    }
+}
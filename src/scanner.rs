@@ -1,5 +1,9 @@
+use std::ops::Range;
 use std::str::Chars;
 
+use crate::dump;
+use crate::int_type::IntType;
+
 #[derive(Clone)]
 pub struct Scanner<'a> {
     buffer: Chars<'a>,
@@ -21,24 +25,60 @@ pub enum Token {
     Mult,
     Modulo,
     PreviousResult,
+    Assign,
+    Question,
+    Colon,
+    Comma,
+    Negate, //emitted by the parser for a prefix minus
+    Store(String), //emitted by the parser for an assignment to the variable
+    Call(String, usize), //emitted by the parser for a call of the function with the number of arguments
+    Cast(IntType), //emitted by the parser for `(u8)x` and `x as u8`
+    AddressOf(String), //emitted by the parser for `&symbol`
+    SizeOf(String), //emitted by the parser for `sizeof(symbol)`
+    ReadFile(String, usize, bool), //emitted by the parser for `read_u32("file", offset)`: the file, the number of bytes and big endian
+    ConvertTo(i64), //emitted by the parser for `x to KiB`: the number of units needed for the value
+    ConvertToPages, //emitted by the parser for `x to pages`
+    AndThen(usize), //emitted by the parser for `&&`: 0 if the lhs is 0, skipping the given number of tokens of the rhs
+    OrElse(usize), //emitted by the parser for `||`: 1 if the lhs is not 0, skipping the given number of tokens of the rhs
+    IsTrue, //emitted by the parser after the rhs of `&&` and `||`, turns the value into 1 or 0
+    JumpIfZero(usize), //emitted by the parser for `?`: skips the given number of tokens if the condition is 0
+    Jump(usize), //emitted by the parser for `:`: skips the given number of tokens
 
     //more character Tokens
     ShiftLeft,
     ShiftRight,
+    LogicalShiftRight,
+    RotateLeft,
+    RotateRight,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    LogicalAnd,
+    LogicalOr,
 
     //Literals
     BinaryNumber(i64),
     DecimalNumber(i64),
     HexNumber(i64),
+    TypedNumber(i64, IntType), //a number with a type suffix like `0xffu8` or `1UL`
+    Unit(i64), //a size unit like `KiB`, multiplies the value in front of it
+    Identifier(String),
+    Text(String), //a string literal like `"dump.bin"`, the file name of a read function
 
     //Keywords
     And,
     Or,
     Xor,
     Nor,
+    Not,
+    As,
+    To,
 
     Error(usize, usize, String),
-    KeywordNotFound,
     Eof,
 }
 
@@ -52,6 +92,8 @@ enum State {
     BinaryNumber,
     HexNumber,
     DecimalNumber,
+    //digit separators and the type suffix of a number with the radix
+    NumberSuffix(u32),
 }
 
 impl<'a> Scanner<'a> {
@@ -92,34 +134,56 @@ impl<'a> Scanner<'a> {
                             return Token::Minus;
                         }
                         Some('&') => {
-                            return Token::And;
+                            return self.either('&', Token::LogicalAnd, Token::And);
                         }
                         Some('|') => {
-                            return Token::Or;
+                            return self.either('|', Token::LogicalOr, Token::Or);
                         }
                         Some('^') => {
                             return Token::Xor;
                         }
                         Some('!') => {
-                            return Token::Bang;
+                            return self.either('=', Token::NotEqual, Token::Bang);
                         }
                         Some('~') => {
                             return Token::TwosComplement;
                         }
                         Some('*') => {
-                            return Token::Mult;
+                            return self.either('*', Token::Power, Token::Mult);
                         }
                         Some('%') => {
                             return Token::Modulo;
                         }
-                        Some('_') => {
+                        Some('_') if !is_identifier_char(self.peek_char()) => {
                             return Token::PreviousResult;
                         }
+                        Some('=') => {
+                            return self.either('=', Token::Equal, Token::Assign);
+                        }
+                        Some('?') => {
+                            return Token::Question;
+                        }
+                        Some(':') => {
+                            return Token::Colon;
+                        }
+                        Some(',') => {
+                            return Token::Comma;
+                        }
+                        Some('"') => {
+                            return self.text(token_start);
+                        }
+                        Some('\'') => {
+                            return self.char_literal(token_start);
+                        }
+                        Some('b') if self.peek_char() == '"' => {
+                            self.buffer.next();
+                            return self.byte_string(token_start);
+                        }
                         Some('>') => state = State::ExpectShiftRight,
                         Some('<') => state = State::ExpectShiftLeft,
                         Some('0') => state = State::ExpectBase,
                         Some('1'..='9') => state = State::DecimalNumber,
-                        Some('a'..='z' | 'A'..='Z') => {
+                        Some('a'..='z' | 'A'..='Z' | '_') => {
                             state = State::Keyword;
                         }
                         None => {
@@ -132,33 +196,44 @@ impl<'a> Scanner<'a> {
                         }
                     }
                 }
-                State::ExpectShiftRight => match self.buffer.next() {
-                    Some('>') => {
+                State::ExpectShiftRight => match self.peek_char() {
+                    '>' => {
+                        self.buffer.next();
+                        if self.peek_char() == '>' {
+                            self.buffer.next();
+                            return self.either('>', Token::RotateRight, Token::LogicalShiftRight);
+                        }
                         return Token::ShiftRight;
                     }
+                    '=' => {
+                        self.buffer.next();
+                        return Token::GreaterEqual;
+                    }
                     _ => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return Token::Error(start, token_len, self.lookup[start..token_len].to_string());
+                        return Token::Greater;
                     }
                 },
-                State::ExpectShiftLeft => match self.buffer.next() {
-                    Some('<') => {
-                        return Token::ShiftLeft;
+                State::ExpectShiftLeft => match self.peek_char() {
+                    '<' => {
+                        self.buffer.next();
+                        return self.either('<', Token::RotateLeft, Token::ShiftLeft);
                     }
-                    None => {
-                        return Token::ShiftLeft;
+                    '=' => {
+                        self.buffer.next();
+                        return Token::LessEqual;
                     }
                     _ => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return Token::Error(start, token_len, self.lookup[start..token_len].to_string());
+                        return Token::Less;
                     }
                 },
                 State::Keyword => {
                     let next_char = self.peek_char();
                     match next_char {
-                        'a'..='z' | 'A'..='Z' => {
+                        ch if is_identifier_char(ch) => {
+                            self.buffer.next();
+                        }
+                        //a dot joins the parts of a qualified name like `GPIOA.ODR`
+                        '.' if self.buffer.clone().nth(1).is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_') => {
                             self.buffer.next();
                         }
                         ch if is_delimiter(ch) => {
@@ -178,10 +253,20 @@ impl<'a> Scanner<'a> {
                         }
                     }
                 }
-                State::ExpectBase => match self.buffer.next() {
-                    Some('b') => state = State::BinaryNumber,
-                    Some('x') => state = State::HexNumber,
+                State::ExpectBase => match self.peek_char() {
+                    'b' => {
+                        state = State::BinaryNumber;
+                        self.buffer.next();
+                    }
+                    'x' => {
+                        state = State::HexNumber;
+                        self.buffer.next();
+                    }
+                    '0'..='9' => state = State::DecimalNumber,
+                    ch if is_delimiter(ch) || ch == EOF_CHAR => return Token::DecimalNumber(0),
+                    ch if is_identifier_char(ch) => state = State::NumberSuffix(10),
                     _ => {
+                        self.buffer.next();
                         let start = self.initial_len - token_start;
                         let token_len = self.initial_len - self.buffer.as_str().len();
                         return Token::Error(start, token_len, self.lookup[start..token_len].to_string());
@@ -193,17 +278,12 @@ impl<'a> Scanner<'a> {
                         self.buffer.next();
                     }
                     ch if is_delimiter(ch) => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::DecimalNumber(self.lookup[start..token_len].parse().unwrap());
+                        return self.number(token_start, 10);
                     }
                     EOF_CHAR => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::DecimalNumber(self.lookup[start..token_len].parse().unwrap());
+                        return self.number(token_start, 10);
                     }
+                    ch if is_identifier_char(ch) => state = State::NumberSuffix(10),
                     _ => {
                         let start = self.initial_len - token_start;
                         let token_len = self.initial_len - self.buffer.as_str().len();
@@ -216,17 +296,12 @@ impl<'a> Scanner<'a> {
                         self.buffer.next();
                     }
                     ch if is_delimiter(ch) => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::BinaryNumber(i64::from_str_radix(&self.lookup[start + 2..token_len], 2).unwrap());
+                        return self.number(token_start, 2);
                     }
                     EOF_CHAR => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::BinaryNumber(i64::from_str_radix(&self.lookup[start + 2..token_len], 2).unwrap());
+                        return self.number(token_start, 2);
                     }
+                    ch if is_identifier_char(ch) => state = State::NumberSuffix(2),
                     _ => {
                         let start = self.initial_len - token_start;
                         let token_len = self.initial_len - self.buffer.as_str().len();
@@ -239,16 +314,24 @@ impl<'a> Scanner<'a> {
                         self.buffer.next();
                     }
                     ch if is_delimiter(ch) => {
-                        let start = self.initial_len - token_start;
-                        let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::HexNumber(i64::from_str_radix(&self.lookup[start + 2..token_len], 16).unwrap());
+                        return self.number(token_start, 16);
                     }
                     EOF_CHAR => {
+                        return self.number(token_start, 16);
+                    }
+                    ch if is_identifier_char(ch) => state = State::NumberSuffix(16),
+                    _ => {
                         let start = self.initial_len - token_start;
                         let token_len = self.initial_len - self.buffer.as_str().len();
-                        return
-                            Token::HexNumber(i64::from_str_radix(&self.lookup[start + 2..token_len], 16).unwrap());
+                        return Token::Error(start, token_len, self.lookup[start..token_len].to_string());
+                    }
+                },
+                State::NumberSuffix(radix) => match self.peek_char() {
+                    ch if is_identifier_char(ch) => {
+                        self.buffer.next();
+                    }
+                    ch if is_delimiter(ch) || ch == EOF_CHAR => {
+                        return self.number(token_start, radix);
                     }
                     _ => {
                         let start = self.initial_len - token_start;
@@ -260,10 +343,132 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    ///returns `matched` and consumes the next character if it is `next`, otherwise returns `single`.
+    fn either(&mut self, next: char, matched: Token, single: Token) -> Token {
+        if self.peek_char() == next {
+            self.buffer.next();
+            matched
+        } else {
+            single
+        }
+    }
+
     pub fn peek(&mut self) -> Token {
         self.clone().next()
     }
 
+    ///turns the number literal that started at `token_start` into a token.
+    ///The digits can be separated by `_` and followed by a type suffix like `u8` or `UL`.
+    ///Literals that do not fit into 64 bits or have an unknown suffix are returned as `Token::Error`.
+    fn number(&mut self, token_start: usize, radix: u32) -> Token {
+        let start = self.initial_len - token_start;
+        let end = self.offset();
+        let error = Token::Error(start, end, self.lookup[start..end].to_string());
+        let literal = if radix == 10 { &self.lookup[start..end] } else { &self.lookup[start + 2..end] };
+        let (digits, suffix) = literal.split_at(literal.find(|ch: char| !ch.is_digit(radix) && ch != '_').unwrap_or(literal.len()));
+        let value = match u64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(value) => value as i64,
+            Err(_) => return error,
+        };
+        //a unit directly after the number like `4MiB` is the next token
+        let suffix = if unit_size(suffix).is_some() {
+            self.buffer = self.lookup[end - suffix.len()..].chars();
+            ""
+        } else {
+            suffix
+        };
+        match suffix.trim_start_matches('_') {
+            "" if radix == 2 => Token::BinaryNumber(value),
+            "" if radix == 16 => Token::HexNumber(value),
+            "" => Token::DecimalNumber(value),
+            suffix => suffix_type(suffix).map_or(error, |int_type| Token::TypedNumber(value, int_type)),
+        }
+    }
+
+    ///returns the string literal that started with the quote at `token_start` up to the closing quote.
+    fn text(&mut self, token_start: usize) -> Token {
+        let start = self.initial_len - token_start;
+        self.eat_while(|ch| ch != '"');
+        if self.buffer.next().is_none() {
+            return Token::Error(start, self.offset(), self.lookup[start..].to_string());
+        }
+        Token::Text(self.lookup[start + 1..self.offset() - 1].to_string())
+    }
+
+    ///turns the char literal like `'A'` or `'\n'` that started at `token_start` into its code point.
+    fn char_literal(&mut self, token_start: usize) -> Token {
+        let start = self.initial_len - token_start;
+        match self.escaped('\'').as_deref() {
+            Some(&[ch]) => Token::DecimalNumber(ch as i64),
+            _ => Token::Error(start, self.offset(), self.lookup[start..self.offset()].to_string()),
+        }
+    }
+
+    ///turns the byte string like `b"ABCD"` that started at `token_start` into the number its bytes make up.
+    ///The first byte is the most significant one unless the string is followed by `le`, like in `b"RIFF"le`,
+    ///the same as reading the bytes from memory as big or little endian value. `be` can be given as well.
+    fn byte_string(&mut self, token_start: usize) -> Token {
+        let start = self.initial_len - token_start;
+        let chars = self.escaped('"');
+        self.eat_while(is_identifier_char);
+        let error = Token::Error(start, self.offset(), self.lookup[start..self.offset()].to_string());
+        let Some(bytes) = chars.and_then(|chars| chars.into_iter().map(|ch| u8::try_from(ch).ok()).collect::<Option<Vec<u8>>>()) else {
+            return error;
+        };
+        let suffix = &self.lookup[self.lookup[..self.offset()].rfind('"').unwrap() + 1..self.offset()];
+        match (suffix, bytes.len()) {
+            (_, 9..) => error,
+            ("" | "be", _) => Token::HexNumber(dump::value(&bytes, true) as i64),
+            ("le", _) => Token::HexNumber(dump::value(&bytes, false) as i64),
+            _ => error,
+        }
+    }
+
+    ///returns the characters up to the closing `quote` with the escape sequences `\n`, `\r`, `\t`, `\0`, `\\`, `\'`,
+    ///`\"`, `\xNN` and `\u{NNNN}` replaced. `None` if the literal is not closed or has an invalid escape sequence.
+    fn escaped(&mut self, quote: char) -> Option<Vec<char>> {
+        let mut chars = Vec::new();
+        loop {
+            let ch = match self.buffer.next()? {
+                ch if ch == quote => return Some(chars),
+                '\\' => match self.buffer.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    ch @ ('\\' | '\'' | '"') => ch,
+                    'x' => {
+                        let digits: String = [self.buffer.next()?, self.buffer.next()?].iter().collect();
+                        char::from(u8::from_str_radix(&digits, 16).ok()?)
+                    }
+                    'u' => {
+                        let rest = self.buffer.as_str().strip_prefix('{')?;
+                        let end = rest.find('}')?;
+                        let ch = char::from_u32(u32::from_str_radix(&rest[..end], 16).ok()?)?;
+                        self.buffer = rest[end + 1..].chars();
+                        ch
+                    }
+                    _ => return None,
+                },
+                ch => ch,
+            };
+            chars.push(ch);
+        }
+    }
+
+    /// like `next`, but also returns the byte range of the token in the input.
+    pub fn next_spanned(&mut self) -> (Token, Range<usize>) {
+        self.eat_while(char::is_whitespace);
+        let start = self.offset();
+        let token = self.next();
+        (token, start..self.offset())
+    }
+
+    /// returns the byte offset of the scanner in the input.
+    pub fn offset(&self) -> usize {
+        self.initial_len - self.buffer.as_str().len()
+    }
+
     fn eat_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
         while predicate(self.peek_char()) && !self.buffer.as_str().is_empty() {
             self.buffer.next();
@@ -281,14 +486,21 @@ impl<'a> Scanner<'a> {
             "nor" | "NOR" => Token::Nor,
             "xor" | "XOR" | "^" => Token::Xor,
             "mod" | "MOD" | "%" => Token::Modulo,
-            _ => Token::KeywordNotFound,
+            "not" | "NOT" => Token::Not,
+            "rol" | "ROL" => Token::RotateLeft,
+            "ror" | "ROR" => Token::RotateRight,
+            "as" => Token::As,
+            "to" => Token::To,
+            unit if unit_size(unit).is_some() => Token::Unit(unit_size(unit).unwrap()),
+            identifier => Token::Identifier(identifier.to_string()),
         }
     }
 }
 
 impl Token {
     pub fn is_operator(&self) -> bool {
-        match self {
+        matches!(
+            self,
             Token::Plus
             | Token::Minus
             | Token::And
@@ -297,31 +509,96 @@ impl Token {
             | Token::Xor
             | Token::ShiftLeft
             | Token::ShiftRight
+            | Token::LogicalShiftRight
+            | Token::RotateLeft
+            | Token::RotateRight
             | Token::Bang
             | Token::TwosComplement
             | Token::LeftParen
             | Token::RightParen
             | Token::Modulo
-            | Token::Mult => true,
-            _ => false,
-        }
+            | Token::Negate
+            | Token::Mult
+            | Token::Power
+            | Token::Equal
+            | Token::NotEqual
+            | Token::Less
+            | Token::LessEqual
+            | Token::Greater
+            | Token::GreaterEqual
+            | Token::LogicalAnd
+            | Token::LogicalOr
+            | Token::Not
+            | Token::IsTrue
+            | Token::Question
+            | Token::Colon
+            | Token::Comma
+            | Token::As
+            | Token::Cast(_)
+            | Token::Unit(_)
+            | Token::To
+            | Token::ConvertTo(_)
+            | Token::ConvertToPages
+        )
     }
 
-    pub fn is_operand(&self) -> bool {
-        match self {
-            Token::DecimalNumber(_) | Token::BinaryNumber(_) | Token::HexNumber(_) => true,
-            _ => false,
-        }
+    /// `true` for operators whose result has the current type, independent of the types of the operands:
+    /// comparisons, logical operators and casts.
+    pub fn is_untyped(&self) -> bool {
+        matches!(
+            self,
+            Token::Equal
+                | Token::NotEqual
+                | Token::Less
+                | Token::LessEqual
+                | Token::Greater
+                | Token::GreaterEqual
+                | Token::Not
+                | Token::IsTrue
+                | Token::Cast(_)
+        )
     }
 
-    pub fn get_value(&self) -> Option<i64> {
-        match self {
-            Token::DecimalNumber(v) | Token::BinaryNumber(v) | Token::HexNumber(v) => Some(*v),
-            _ => None,
-        }
+    /// `true` for operators that take a single operand.
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Token::Bang | Token::TwosComplement | Token::Negate | Token::Not | Token::IsTrue | Token::Cast(_) | Token::Unit(_) | Token::ConvertTo(_) | Token::ConvertToPages)
     }
 }
 
+///returns the type of a literal with the suffix: a Rust type like `u8` or `i64`, or a C suffix like `U`, `L`, `UL` or `ULL`
+///with the sizes of 64 bit Linux.
+fn suffix_type(suffix: &str) -> Option<IntType> {
+    let (width, signed) = match suffix.to_ascii_lowercase().as_str() {
+        "usize" => (64, false),
+        "isize" => (64, true),
+        "u" => (32, false),
+        "l" | "ll" => (64, true),
+        "ul" | "lu" | "ull" | "llu" => (64, false),
+        _ => return IntType::from_name(suffix),
+    };
+    IntType::new(width, signed)
+}
+
+///returns the number of bytes of the size unit `unit`, e.g. 1024 for `KiB`.
+pub fn unit_size(unit: &str) -> Option<i64> {
+    let size = match unit {
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        "kB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => return None,
+    };
+    Some(size)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
 fn is_delimiter(c: char) -> bool {
     matches!(
         c,
@@ -338,6 +615,10 @@ fn is_delimiter(c: char) -> bool {
             | '^'
             | '*'
             | '%'
+            | '='
+            | '?'
+            | ':'
+            | ','
              //whitespaces:
             | '\u{0009}'   // \t
             | '\u{000A}' // \n
@@ -415,7 +696,7 @@ mod tests {
     #[test]
     fn test_dec_number() {
         let mut sc = Scanner::new("1234");
-        assert_eq!(sc.next(), Token::DecimalNumber(i64::from_str_radix(&"1234", 10).unwrap()));
+        assert_eq!(sc.next(), Token::DecimalNumber("1234".parse().unwrap()));
     }
 
     #[test]
@@ -461,7 +742,7 @@ mod tests {
         assert_eq!(sc.next(), Token::LeftParen);
         assert_eq!(sc.next(), Token::BinaryNumber(i64::from_str_radix("1010", 2).unwrap()));
         assert_eq!(sc.next(), Token::Plus);
-        assert_eq!(sc.next(), Token::HexNumber(i64::from_str_radix(&"ff", 16).unwrap()));
+        assert_eq!(sc.next(), Token::HexNumber(i64::from_str_radix("ff", 16).unwrap()));
         assert_eq!(sc.next(), Token::RightParen);
         assert_eq!(sc.next(), Token::And);
         assert_eq!(sc.next(), Token::LeftParen);
@@ -481,6 +762,159 @@ mod tests {
         assert_eq!(sc.next(), Token::DecimalNumber(3));
     }
 
+    #[test]
+    fn test_number_limits() {
+        let mut sc = Scanner::new("0xffffffffffffffff 0x10000000000000000 0x");
+        assert_eq!(sc.next(), Token::HexNumber(-1));
+        assert_eq!(sc.next(), Token::Error(19, 38, "0x10000000000000000".to_string()));
+        assert_eq!(sc.next(), Token::Error(39, 41, "0x".to_string()));
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let mut sc = Scanner::new("page_size = _ + _x1 and XOR");
+        assert_eq!(sc.next(), Token::Identifier("page_size".to_string()));
+        assert_eq!(sc.next(), Token::Assign);
+        assert_eq!(sc.next(), Token::PreviousResult);
+        assert_eq!(sc.next(), Token::Plus);
+        assert_eq!(sc.next(), Token::Identifier("_x1".to_string()));
+        assert_eq!(sc.next(), Token::And);
+        assert_eq!(sc.next(), Token::Xor);
+
+        let mut sc = Scanner::new("GPIOA.ODR+4");
+        assert_eq!(sc.next(), Token::Identifier("GPIOA.ODR".to_string()));
+        assert_eq!(sc.next(), Token::Plus);
+    }
+
+    #[test]
+    fn test_text() {
+        let mut sc = Scanner::new("read_u8(\"dump 1.bin\", 4) \"open");
+        assert_eq!(sc.next(), Token::Identifier("read_u8".to_string()));
+        assert_eq!(sc.next(), Token::LeftParen);
+        assert_eq!(sc.next(), Token::Text("dump 1.bin".to_string()));
+        assert_eq!(sc.next(), Token::Comma);
+        assert_eq!(sc.next(), Token::DecimalNumber(4));
+        assert_eq!(sc.next(), Token::RightParen);
+        assert_eq!(sc.next(), Token::Error(25, 30, "\"open".to_string()));
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let mut sc = Scanner::new("'A'+'\\n' '\\x1b' '\\u{e9}' '\\'' 'AB'");
+        assert_eq!(sc.next(), Token::DecimalNumber(0x41));
+        assert_eq!(sc.next(), Token::Plus);
+        assert_eq!(sc.next(), Token::DecimalNumber(0x0a));
+        assert_eq!(sc.next(), Token::DecimalNumber(0x1b));
+        assert_eq!(sc.next(), Token::DecimalNumber(0xe9));
+        assert_eq!(sc.next(), Token::DecimalNumber(0x27));
+        assert_eq!(sc.next(), Token::Error(30, 34, "'AB'".to_string()));
+        let mut sc = Scanner::new("'\\q' 'A");
+        assert_eq!(sc.next(), Token::Error(0, 3, "'\\q".to_string()));
+    }
+
+    #[test]
+    fn test_byte_strings() {
+        let mut sc = Scanner::new("b\"ABCD\" b\"RIFF\"le b\"\\x00\\xff\"be b\"\" b\"123456789\" b\"A\"ne b");
+        assert_eq!(sc.next(), Token::HexNumber(0x41424344));
+        assert_eq!(sc.next(), Token::HexNumber(0x46464952));
+        assert_eq!(sc.next(), Token::HexNumber(0xff));
+        assert_eq!(sc.next(), Token::HexNumber(0));
+        assert_eq!(sc.next(), Token::Error(36, 48, "b\"123456789\"".to_string()));
+        assert_eq!(sc.next(), Token::Error(49, 55, "b\"A\"ne".to_string()));
+        assert_eq!(sc.next(), Token::Identifier("b".to_string()));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut sc = Scanner::new("a==1 != b<c <= d>e >= f && g||h ? not i : j = k&l|m");
+        let tokens: Vec<_> = std::iter::from_fn(|| Some(sc.next()).filter(|token| *token != Token::Eof))
+            .filter(|token| !matches!(token, Token::Identifier(_) | Token::DecimalNumber(_)))
+            .collect();
+        assert_eq!(
+            vec![
+                Token::Equal,
+                Token::NotEqual,
+                Token::Less,
+                Token::LessEqual,
+                Token::Greater,
+                Token::GreaterEqual,
+                Token::LogicalAnd,
+                Token::LogicalOr,
+                Token::Question,
+                Token::Not,
+                Token::Colon,
+                Token::Assign,
+                Token::And,
+                Token::Or,
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_power() {
+        let mut sc = Scanner::new("3**5*pow(2,3)");
+        assert_eq!(sc.next(), Token::DecimalNumber(3));
+        assert_eq!(sc.next(), Token::Power);
+        assert_eq!(sc.next(), Token::DecimalNumber(5));
+        assert_eq!(sc.next(), Token::Mult);
+        assert_eq!(sc.next(), Token::Identifier("pow".to_string()));
+        assert_eq!(sc.next(), Token::LeftParen);
+        assert_eq!(sc.next(), Token::DecimalNumber(2));
+        assert_eq!(sc.next(), Token::Comma);
+    }
+
+    #[test]
+    fn test_rotations() {
+        let mut sc = Scanner::new("a>>>1 >>>> 2<<<3 rol 4 ROR 5 >> 6");
+        let tokens: Vec<_> = std::iter::from_fn(|| Some(sc.next()).filter(|token| *token != Token::Eof))
+            .filter(|token| token.is_operator())
+            .collect();
+        assert_eq!(
+            vec![
+                Token::LogicalShiftRight,
+                Token::RotateRight,
+                Token::RotateLeft,
+                Token::RotateLeft,
+                Token::RotateRight,
+                Token::ShiftRight,
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_typed_numbers() {
+        let u8 = IntType::new(8, false).unwrap();
+        let mut sc = Scanner::new("0xFFu8 255_i16 1UL 0x10ull 0b1010_u8 1_000 0u 7usize");
+        assert_eq!(sc.next(), Token::TypedNumber(0xff, u8));
+        assert_eq!(sc.next(), Token::TypedNumber(255, IntType::new(16, true).unwrap()));
+        assert_eq!(sc.next(), Token::TypedNumber(1, IntType::new(64, false).unwrap()));
+        assert_eq!(sc.next(), Token::TypedNumber(0x10, IntType::new(64, false).unwrap()));
+        assert_eq!(sc.next(), Token::TypedNumber(0b1010, u8));
+        assert_eq!(sc.next(), Token::DecimalNumber(1000));
+        assert_eq!(sc.next(), Token::TypedNumber(0, IntType::new(32, false).unwrap()));
+        assert_eq!(sc.next(), Token::TypedNumber(7, IntType::new(64, false).unwrap()));
+        let mut sc = Scanner::new("12u7 0b12 0xu8");
+        assert_eq!(sc.next(), Token::Error(0, 4, "12u7".to_string()));
+        assert_eq!(sc.next(), Token::Error(5, 9, "0b12".to_string()));
+        assert_eq!(sc.next(), Token::Error(10, 14, "0xu8".to_string()));
+    }
+
+    #[test]
+    fn test_units() {
+        let mut sc = Scanner::new("4MiB + 2 kB to KiB 0x10GiB");
+        assert_eq!(sc.next(), Token::DecimalNumber(4));
+        assert_eq!(sc.next(), Token::Unit(1 << 20));
+        assert_eq!(sc.next(), Token::Plus);
+        assert_eq!(sc.next(), Token::DecimalNumber(2));
+        assert_eq!(sc.next(), Token::Unit(1000));
+        assert_eq!(sc.next(), Token::To);
+        assert_eq!(sc.next(), Token::Unit(1024));
+        assert_eq!(sc.next(), Token::HexNumber(0x10));
+        assert_eq!(sc.next(), Token::Unit(1 << 30));
+    }
+
     #[test]
     fn test_zero_decimal() {
         let mut sc = Scanner::new("0");
@@ -0,0 +1,362 @@
+use crate::cli::{self, Options, OutputFormat};
+use crate::error::Error;
+use crate::format;
+use crate::json;
+use crate::vm::VM;
+
+/// marks the start of the comment with the result, that `annotate` appends to a line.
+pub const ANNOTATION_MARKER: &str = "# =>";
+/// statement that checks a value: `assert value == expected` or `assert condition`
+const ASSERT: &str = "assert";
+/// statement that checks the result of the previous statement: `expect expected`
+const EXPECT: &str = "expect";
+
+/// A statement of a script file.
+/// Statements are separated by newlines or `;`, everything after `#` up to the end of the line is a comment,
+/// except in quotes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement<'a> {
+    pub text: &'a str,
+    /// byte offset of the statement in the script
+    pub offset: usize,
+}
+
+/// splits `source` into its statements, leaving out comments and empty statements.
+/// A `;` or `#` in a char literal, a byte string or a quoted file name like `'#'` or `"a;b.bin"` is part of the statement.
+pub fn statements(source: &str) -> Vec<Statement<'_>> {
+    let mut statements = Vec::new();
+    let mut line_offset = 0;
+    for line in source.split_inclusive('\n') {
+        let mut push = |start: usize, end: usize| {
+            let part = &line[start..end];
+            let text = part.trim();
+            if !text.is_empty() {
+                let leading_whitespace = part.len() - part.trim_start().len();
+                statements.push(Statement { text, offset: line_offset + start + leading_whitespace });
+            }
+        };
+        let mut start = 0;
+        let mut end = line.len();
+        for (index, ch) in unquoted(line) {
+            match ch {
+                ';' => {
+                    push(start, index);
+                    start = index + 1;
+                }
+                '#' => {
+                    end = index;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        push(start, end);
+        line_offset += line.len();
+    }
+    statements
+}
+
+/// returns the 1-based line and column of the byte `offset` in `source`.
+pub fn location(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// formats the byte `offset` of `source` as `file:line:col`.
+fn position(file_name: &str, source: &str, offset: usize) -> String {
+    let (line, column) = location(source, offset);
+    format!("{}:{}:{}", file_name, line, column)
+}
+
+/// evaluates a statement of a script. Besides expressions, a statement can be a check:
+/// `assert value == expected`, `assert condition`, which has to be non-zero, or `expect expected`,
+/// which compares the result of the previous statement. A check returns `None` if it holds, a check
+/// that does not hold is an `Error::AssertionFailed`. Checks change neither the previous result nor the variables.
+pub fn execute(vm: &mut VM, text: &str) -> Result<Option<i64>, Error> {
+    let (is_assert, argument) = match (keyword_argument(text, ASSERT), keyword_argument(text, EXPECT)) {
+        (Some(argument), _) => (true, argument),
+        (_, Some(argument)) => (false, argument),
+        _ => return vm.run(text).map(Some),
+    };
+    let span = offset_in(text, argument)..text.len();
+    let int_type = vm.int_type();
+    let summary = |value: i64| format::summary(value, int_type);
+    let previous = vm.previous_result();
+    //the sides of a check are queries, so neither the previous result nor the variables change
+    let evaluate = |expression: &str| vm.query(expression).map_err(|err| err.shifted(offset_in(text, expression)));
+    let mismatch = if is_assert {
+        match comparison(argument) {
+            Some(operator) => {
+                let (actual, expected) = (argument[..operator].trim_end(), argument[operator + 2..].trim_start());
+                let (actual_value, expected_value) = (evaluate(actual)?, evaluate(expected)?);
+                (actual_value != expected_value)
+                    .then(|| format!("`{}` is {}, expected {}", actual, summary(actual_value), summary(expected_value)))
+            }
+            None => (evaluate(argument)? == 0).then(|| format!("`{}` is 0", argument)),
+        }
+    } else {
+        let actual_value = previous.ok_or(Error::AssertionFailed(span.clone(), "`expect` needs a previous result".to_string()))?;
+        let expected_value = evaluate(argument)?;
+        (actual_value != expected_value).then(|| format!("expected {}, got {}", summary(expected_value), summary(actual_value)))
+    };
+    match mismatch {
+        Some(message) => Err(Error::AssertionFailed(span, message)),
+        None => Ok(None),
+    }
+}
+
+/// returns the byte offset of `part`, which has to be a slice of `text`, in `text`.
+fn offset_in(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// returns the position of the first `==` that is neither inside parens nor in quotes like `b"=="`.
+fn comparison(text: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    for (index, ch) in unquoted(text) {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '=' if depth == 0 && text[index..].starts_with("==") => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// returns the characters of `text` with their byte offsets, leaving out char literals, byte strings and quoted
+/// file names like `'#'` or `"a;b.bin"` including the quotes. An escaped quote like in `'\''` does not close them.
+fn unquoted(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    text.char_indices().filter(move |(_, ch)| {
+        match (quote, *ch) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), ch) if ch == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(*ch),
+            (None, _) => return true,
+        }
+        false
+    })
+}
+
+/// returns the argument of the statement `text`, if it starts with `keyword`.
+fn keyword_argument<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(keyword)?;
+    //`asserted = 1` is an assignment, not a check
+    rest.starts_with(char::is_whitespace).then(|| rest.trim_start())
+}
+
+/// runs every statement of the script `source` and prints its result.
+/// Errors and warnings are reported as `file:line:col`, statements after an error are still evaluated.
+/// Returns the exit code.
+pub fn run(vm: &mut VM, file_name: &str, source: &str, options: &Options) -> i32 {
+    let mut exit_code = cli::EXIT_SUCCESS;
+    for statement in statements(source) {
+        let result = execute(vm, statement.text);
+        let int_type = vm.int_type();
+        match (&result, options.format) {
+            (Ok(None), OutputFormat::Json) => println!("{{\"input\":{},\"ok\":true}}", json::string(statement.text)),
+            (Ok(Some(value)), OutputFormat::Json) => println!("{}", json::evaluation(statement.text, &Ok(*value), vm)),
+            (Err(err), OutputFormat::Json) => println!("{}", json::evaluation(statement.text, &Err(err.clone()), vm)),
+            (Ok(None), OutputFormat::All) => println!("{} => ok", statement.text),
+            (Ok(None), OutputFormat::Radix(_)) => {}
+            (Ok(Some(value)), OutputFormat::All) => println!("{} => {}", statement.text, format::summary(*value, int_type)),
+            (Ok(Some(value)), OutputFormat::Radix(radix)) => {
+                println!("{}", format::format(*value, radix, &format::FormatOptions::default(), int_type))
+            }
+            (Err(err), _) => eprintln!("{}", diagnostic(file_name, source, &statement, err)),
+        }
+        match result {
+            Ok(Some(value)) if options.format != OutputFormat::Json => {
+                for warning in vm.warnings() {
+                    eprintln!("{}: warning: {}", position(file_name, source, statement.offset + warning.span.start), warning.message);
+                }
+                for mode in &options.output_modes {
+                    println!("{}", mode.render(value, int_type.width, vm.page_size()));
+                }
+            }
+            Ok(_) => {}
+            Err(_) => exit_code = cli::EXIT_EVALUATION_ERROR,
+        }
+    }
+    exit_code
+}
+
+/// runs the script `source` as a test and returns a diagnostic for every statement that failed,
+/// either because it could not be evaluated or because a check did not hold. An empty list means the test passed.
+pub fn test(vm: &mut VM, file_name: &str, source: &str) -> Vec<String> {
+    statements(source)
+        .iter()
+        .filter_map(|statement| execute(vm, statement.text).err().map(|err| diagnostic(file_name, source, statement, &err)))
+        .collect()
+}
+
+/// formats `err` of `statement` as `file:line:col: error: message`.
+fn diagnostic(file_name: &str, source: &str, statement: &Statement, err: &Error) -> String {
+    let span_start = err.span().map_or(statement.text.len(), |span| span.start);
+    format!("{}: error: {}", position(file_name, source, statement.offset + span_start), err)
+}
+
+/// evaluates `source` line by line and returns it with the result of every line appended as comment,
+/// e.g. `size = 8000  # => 0x1f40 (8000)`. The comments are aligned and annotations of a previous
+/// run are replaced. Lines with more than one statement are annotated with the result of the last one.
+/// The second value is `false` if any statement could not be evaluated.
+pub fn annotate(vm: &mut VM, source: &str) -> (String, bool) {
+    let mut success = true;
+    let mut lines = Vec::new();
+    for line in source.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let newline = &line[content.len()..];
+        let content = match content.find(ANNOTATION_MARKER) {
+            Some(marker) => content[..marker].trim_end(),
+            None => content,
+        };
+        let mut annotation = None;
+        for statement in statements(content) {
+            match execute(vm, statement.text) {
+                Ok(Some(value)) => annotation = Some(format::summary(value, vm.int_type())),
+                Ok(None) => annotation = Some("ok".to_string()),
+                Err(err) => {
+                    success = false;
+                    annotation = Some(format!("error: {}", err));
+                    break;
+                }
+            }
+        }
+        lines.push((content, annotation, newline));
+    }
+
+    let column = lines
+        .iter()
+        .filter(|(_, annotation, _)| annotation.is_some())
+        .map(|(content, _, _)| content.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let mut output = String::with_capacity(source.len());
+    for (content, annotation, newline) in lines {
+        match annotation {
+            Some(annotation) => output.push_str(&format!("{:<column$}{} {}", content, ANNOTATION_MARKER, annotation, column = column)),
+            None => output.push_str(content),
+        }
+        output.push_str(newline);
+    }
+    (output, success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statements() {
+        let source = "# page math\npage = 4096\n  pages = 3; size = page * pages # bytes\n\n;size\n";
+        let actual = statements(source);
+        assert_eq!(
+            vec![
+                Statement { text: "page = 4096", offset: 12 },
+                Statement { text: "pages = 3", offset: 26 },
+                Statement { text: "size = page * pages", offset: 37 },
+                Statement { text: "size", offset: 67 },
+            ],
+            actual
+        );
+        for statement in actual {
+            assert_eq!(statement.text, &source[statement.offset..statement.offset + statement.text.len()]);
+        }
+    }
+
+    #[test]
+    fn test_statements_with_quotes() {
+        let source = "x = '#'; y = ';' # comment\nb\";#\"; read_u8(\"a;b.bin\", 0)\nz = '\\'' + 1; '\"' # \"\n";
+        let texts: Vec<_> = statements(source).iter().map(|statement| statement.text).collect();
+        assert_eq!(vec!["x = '#'", "y = ';'", "b\";#\"", "read_u8(\"a;b.bin\", 0)", "z = '\\'' + 1", "'\"'"], texts);
+        let mut vm = VM::new();
+        assert_eq!(Ok(Some('#' as i64)), execute(&mut vm, statements(source)[0].text));
+        assert_eq!(Ok(Some(';' as i64)), execute(&mut vm, statements(source)[1].text));
+    }
+
+    #[test]
+    fn test_location() {
+        let source = "1 + 1\nx = 3 % 0\n";
+        assert_eq!((1, 1), location(source, 0));
+        assert_eq!((2, 7), location(source, 12));
+        assert_eq!((3, 1), location(source, source.len()));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let source = "# flash layout\nsector = 4 * 1024\nsectors = 2; sector * sectors # total\n\nsector % 0\n";
+        let (annotated, success) = annotate(&mut VM::new(), source);
+        assert!(!success);
+        assert_eq!(
+            "# flash layout\n\
+             sector = 4 * 1024                      # => 0x1000 (4096)\n\
+             sectors = 2; sector * sectors # total  # => 0x2000 (8192)\n\
+             \n\
+             sector % 0                             # => error: division by zero\n",
+            annotated
+        );
+        //annotating again replaces the old annotations
+        let (reannotated, _) = annotate(&mut VM::new(), &annotated.replace("4 * 1024", "8 * 1024"));
+        assert!(reannotated.contains("sector = 8 * 1024                      # => 0x2000 (8192)\n"));
+        assert_eq!(annotated.lines().count(), reannotated.lines().count());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut vm = VM::new();
+        let options = Options::default();
+        assert_eq!(cli::EXIT_SUCCESS, run(&mut vm, "test.calc", "a = 2; b = a << 4\nb + 1", &options));
+        assert_eq!(Some(33), vm.previous_result());
+        assert_eq!(cli::EXIT_EVALUATION_ERROR, run(&mut vm, "test.calc", "1 % 0\n2", &options));
+        assert_eq!(Some(2), vm.previous_result());
+    }
+
+    #[test]
+    fn test_checks() {
+        let mut vm = VM::new();
+        assert_eq!(Ok(Some(16384)), execute(&mut vm, "size = 4 * 4096"));
+        assert_eq!(Ok(None), execute(&mut vm, "assert size == 0x4000"));
+        assert_eq!(Ok(None), execute(&mut vm, "expect 16 * 1024"));
+        assert_eq!(Ok(None), execute(&mut vm, "assert size"));
+        assert_eq!(Ok(None), execute(&mut vm, "assert (size == 0x4000) == 1"));
+        assert_eq!(Ok(None), execute(&mut vm, "assert size > 0x1000 && size & 0xfff == 0"));
+        //checks keep the previous result
+        assert_eq!(Some(16384), vm.previous_result());
+        assert_eq!(
+            Err(Error::AssertionFailed(7..26, "`size >> 1` is 0x2000 (8192), expected 0x4000 (16384)".to_string())),
+            execute(&mut vm, "assert size >> 1 == 0x4000")
+        );
+        assert_eq!(Err(Error::AssertionFailed(7..8, "expected 0x1 (1), got 0x4000 (16384)".to_string())), execute(&mut vm, "expect 1"));
+        assert_eq!(Err(Error::DivisionByZero(17..18)), execute(&mut vm, "assert size == 1 % 0"));
+        assert_eq!(Ok(Some(1)), execute(&mut vm, "asserted = 1"));
+        assert!(execute(&mut VM::new(), "expect 1").is_err());
+        assert_eq!(Ok(None), execute(&mut vm, "assert b\"==\" == 0x3d3d"));
+        assert_eq!(Ok(None), execute(&mut vm, "assert '=' + ('\\'' == 39) == 62"));
+        assert_eq!(Some(5), comparison("'\\'' == '='"));
+        let mut vm = VM::new();
+        assert_eq!(Ok(None), execute(&mut vm, "assert 2 == 2"));
+        assert_eq!(Ok(Some(1)), execute(&mut vm, "_ + 1"));
+        assert_eq!(Err(Error::DivisionByZero(14..15)), execute(&mut vm, "assert 5 == 1 % 0"));
+        assert_eq!(Ok(None), execute(&mut vm, "expect 1"));
+    }
+
+    #[test]
+    fn test_test() {
+        let source = "page = 4096\nassert page == 0x1000\npage * 2\nexpect 0x1000\nassert page - page\n";
+        assert_eq!(
+            vec![
+                "regs.calc:4:8: error: assertion failed: expected 0x1000 (4096), got 0x2000 (8192)".to_string(),
+                "regs.calc:5:8: error: assertion failed: `page - page` is 0".to_string(),
+            ],
+            test(&mut VM::new(), "regs.calc", source)
+        );
+        assert!(test(&mut VM::new(), "regs.calc", "assert 1 + 1 == 2").is_empty());
+    }
+}
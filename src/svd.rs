@@ -0,0 +1,253 @@
+use crate::int_type::IntType;
+use crate::registers::{self, Field, Register};
+use crate::xml::{self, Element};
+
+/// the size of registers if neither the device, the peripheral nor the register has a `size`
+const DEFAULT_SIZE: i64 = 32;
+
+/// The peripherals and registers of a device described in a CMSIS-SVD file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Device {
+    /// the names and base addresses of the peripherals
+    pub peripherals: Vec<(String, i64)>,
+    /// the registers of every peripheral, named `PERIPHERAL.REGISTER` with their absolute address
+    pub registers: Vec<Register>,
+}
+
+/// reads the device described by the SVD file `file_name`.
+pub fn load(file_name: &str) -> Result<Device, String> {
+    let source = std::fs::read_to_string(file_name).map_err(|err| format!("could not read {}: {}", file_name, err))?;
+    parse(&source).map_err(|err| format!("{}:{}", file_name, err))
+}
+
+/// parses an SVD document. Register arrays (`dim`) are expanded, a peripheral `derivedFrom` another one without
+/// registers of its own gets the registers of the other one. Clusters of registers are not supported and left out.
+pub fn parse(source: &str) -> Result<Device, String> {
+    let device = xml::parse(source)?;
+    if device.name != "device" {
+        return Err(format!("expected the element `device`, found `{}`", device.name));
+    }
+    let default_size = optional_integer(&device, "size")?.unwrap_or(DEFAULT_SIZE);
+    let peripherals: Vec<&Element> = device.child("peripherals").map(|peripherals| peripherals.children("peripheral").collect()).unwrap_or_default();
+    let mut result = Device { peripherals: Vec::new(), registers: Vec::new() };
+    for peripheral in &peripherals {
+        let name = text(peripheral, "name")?;
+        let base = integer(peripheral, "baseAddress").map_err(|err| format!("peripheral `{}`: {}", name, err))?;
+        let mut template = *peripheral;
+        if let (Some(derived_from), None) = (peripheral.attribute("derivedFrom"), peripheral.child("registers")) {
+            template = peripherals
+                .iter()
+                .find(|other| other.child_text("name") == Some(derived_from))
+                .ok_or(format!("peripheral `{}` is derived from the unknown peripheral `{}`", name, derived_from))?;
+        }
+        let size = optional_integer(template, "size")?.unwrap_or(default_size);
+        for register in template.child("registers").iter().flat_map(|registers| registers.children("register")) {
+            for (register_name, offset) in instances(register)? {
+                let register = self::register(register, size).map_err(|err| format!("register `{}.{}`: {}", name, register_name, err))?;
+                result.registers.push(Register {
+                    name: format!("{}.{}", name, register_name),
+                    address: Some(base.wrapping_add(offset)),
+                    ..register
+                });
+            }
+        }
+        result.peripherals.push((name.to_string(), base));
+    }
+    Ok(result)
+}
+
+/// returns the register without name and address.
+fn register(register: &Element, size: i64) -> Result<Register, String> {
+    let width = optional_integer(register, "size")?.unwrap_or(size);
+    let width = u32::try_from(width).ok().filter(|width| IntType::new(*width, false).is_some()).ok_or(format!("unsupported size {}", width))?;
+    let mut fields = Vec::new();
+    for field in register.child("fields").iter().flat_map(|fields| fields.children("field")) {
+        let name = text(field, "name")?;
+        let (high, low) = bit_range(field).map_err(|err| format!("field `{}`: {}", name, err))?;
+        if high >= width || low > high {
+            return Err(format!("field `{}` does not fit into {} bits", name, width));
+        }
+        let mut values = Vec::new();
+        for value in field.children("enumeratedValues").flat_map(|values| values.children("enumeratedValue")) {
+            //values with don't care bits like `#1x` and the default value have no single value to show
+            if let (Some(value_name), Some(Some(value))) = (value.child_text("name"), value.child_text("value").map(enumerated_value)) {
+                values.push((value_name.to_string(), value));
+            }
+        }
+        fields.push(Field { name: name.to_string(), high, low, values });
+    }
+    fields.sort_by_key(|field| std::cmp::Reverse(field.high));
+    Ok(Register { name: String::new(), address: None, width, fields })
+}
+
+/// returns the names and address offsets of the register, which are several for an array with `dim`.
+/// The `%s` in the name of an array is replaced by the index, `[%s]` by the index without brackets.
+fn instances(register: &Element) -> Result<Vec<(String, i64)>, String> {
+    let name = text(register, "name")?;
+    let offset = integer(register, "addressOffset").map_err(|err| format!("register `{}`: {}", name, err))?;
+    let Some(dim) = optional_integer(register, "dim")? else {
+        return Ok(vec![(name.to_string(), offset)]);
+    };
+    let increment = integer(register, "dimIncrement")?;
+    let indices: Vec<String> = match register.child_text("dimIndex") {
+        //either a list like `A,B,C` or a range like `0-3`
+        Some(indices) => match indices.split_once('-').map(|(first, last)| (first.parse::<i64>(), last.parse::<i64>())) {
+            Some((Ok(first), Ok(last))) => (first..=last).map(|index| index.to_string()).collect(),
+            _ => indices.split(',').map(|index| index.trim().to_string()).collect(),
+        },
+        None => (0..dim).map(|index| index.to_string()).collect(),
+    };
+    indices
+        .iter()
+        .enumerate()
+        .map(|(position, index)| {
+            let offset = (position as i64).checked_mul(increment).and_then(|distance| distance.checked_add(offset));
+            let offset = offset.ok_or(format!("register `{}`: the address offset of index {} is out of range", name, index))?;
+            Ok((name.replace("[%s]", index).replace("%s", index), offset))
+        })
+        .collect()
+}
+
+/// returns the highest and the lowest bit of a field, which are given in one of three ways:
+/// `bitOffset` and `bitWidth`, `lsb` and `msb` or `bitRange` like `[7:4]`.
+fn bit_range(field: &Element) -> Result<(u32, u32), String> {
+    let bit = |value: i64| u32::try_from(value).ok().filter(|bit| *bit < 64).ok_or(format!("invalid bit {}", value));
+    if let Some(offset) = optional_integer(field, "bitOffset")? {
+        let width = optional_integer(field, "bitWidth")?.unwrap_or(1);
+        let high = offset.checked_add(width).and_then(|end| end.checked_sub(1)).ok_or(format!("invalid bit width {} at bit offset {}", width, offset))?;
+        return Ok((bit(high)?, bit(offset)?));
+    }
+    if let (Some(lsb), Some(msb)) = (optional_integer(field, "lsb")?, optional_integer(field, "msb")?) {
+        return Ok((bit(msb)?, bit(lsb)?));
+    }
+    let range = text(field, "bitRange")?;
+    let (msb, lsb) = range
+        .strip_prefix('[')
+        .and_then(|range| range.strip_suffix(']'))
+        .and_then(|range| range.split_once(':'))
+        .and_then(|(msb, lsb)| Some((msb.parse().ok()?, lsb.parse().ok()?)))
+        .ok_or(format!("invalid bit range `{}`", range))?;
+    Ok((bit(msb)?, bit(lsb)?))
+}
+
+/// parses the value of an enumerated value, which can also be binary like `#0101`.
+fn enumerated_value(text: &str) -> Option<u64> {
+    match text.strip_prefix('#') {
+        Some(binary) => u64::from_str_radix(binary, 2).ok(),
+        None => registers::parse_integer(text).map(|value| value as u64),
+    }
+}
+
+fn text<'a>(element: &'a Element, name: &str) -> Result<&'a str, String> {
+    element.child_text(name).ok_or(format!("`{}` has no `{}`", element.name, name))
+}
+
+fn integer(element: &Element, name: &str) -> Result<i64, String> {
+    optional_integer(element, name)?.ok_or(format!("`{}` has no `{}`", element.name, name))
+}
+
+fn optional_integer(element: &Element, name: &str) -> Result<Option<i64>, String> {
+    match element.child_text(name) {
+        Some(text) => registers::parse_integer(text).map(Some).ok_or(format!("invalid number `{}` in `{}`", text, name)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1">
+  <name>STM32F401</name>
+  <size>32</size>
+  <peripherals>
+    <peripheral>
+      <name>GPIOA</name>
+      <baseAddress>0x40020000</baseAddress>
+      <registers>
+        <register>
+          <name>MODER</name>
+          <addressOffset>0x0</addressOffset>
+          <fields>
+            <field>
+              <name>MODER1</name>
+              <bitOffset>2</bitOffset>
+              <bitWidth>2</bitWidth>
+              <enumeratedValues>
+                <enumeratedValue><name>Input</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>Output</name><value>#01</value></enumeratedValue>
+                <enumeratedValue><name>Any</name><value>#1x</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+            <field><name>MODER0</name><bitRange>[1:0]</bitRange></field>
+          </fields>
+        </register>
+        <register>
+          <name>ODR</name>
+          <addressOffset>0x14</addressOffset>
+          <size>16</size>
+          <fields>
+            <field><name>ODR15</name><lsb>15</lsb><msb>15</msb></field>
+          </fields>
+        </register>
+        <register>
+          <dim>2</dim>
+          <dimIncrement>4</dimIncrement>
+          <name>AFR[%s]</name>
+          <addressOffset>0x20</addressOffset>
+        </register>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="GPIOA">
+      <name>GPIOB</name>
+      <baseAddress>0x40020400</baseAddress>
+    </peripheral>
+  </peripherals>
+</device>
+"#;
+
+    #[test]
+    fn test_parse() {
+        let device = parse(DEVICE).unwrap();
+        assert_eq!(vec![("GPIOA".to_string(), 0x40020000), ("GPIOB".to_string(), 0x40020400)], device.peripherals);
+        let names: Vec<_> = device.registers.iter().map(|register| (register.name.as_str(), register.address.unwrap())).collect();
+        assert_eq!(
+            vec![
+                ("GPIOA.MODER", 0x40020000),
+                ("GPIOA.ODR", 0x40020014),
+                ("GPIOA.AFR0", 0x40020020),
+                ("GPIOA.AFR1", 0x40020024),
+                ("GPIOB.MODER", 0x40020400),
+                ("GPIOB.ODR", 0x40020414),
+                ("GPIOB.AFR0", 0x40020420),
+                ("GPIOB.AFR1", 0x40020424),
+            ],
+            names
+        );
+        let moder = &device.registers[0];
+        assert_eq!(32, moder.width);
+        assert_eq!((3, 2), (moder.fields[0].high, moder.fields[0].low));
+        assert_eq!(vec![("Input".to_string(), 0), ("Output".to_string(), 1)], moder.fields[0].values);
+        assert_eq!((1, 0), (moder.fields[1].high, moder.fields[1].low));
+        assert_eq!(16, device.registers[1].width);
+        assert_eq!(0x8000, device.registers[1].fields[0].mask());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("<svd/>").is_err());
+        let missing_base = "<device><peripherals><peripheral><name>A</name></peripheral></peripherals></device>";
+        assert_eq!(Err("peripheral `A`: `peripheral` has no `baseAddress`".to_string()), parse(missing_base));
+        let unknown_base = "<device><peripherals><peripheral derivedFrom='X'><name>A</name><baseAddress>0</baseAddress></peripheral></peripherals></device>";
+        assert_eq!(Err("peripheral `A` is derived from the unknown peripheral `X`".to_string()), parse(unknown_base));
+        let field = |bits: &str| format!("<device><peripherals><peripheral><name>A</name><baseAddress>0</baseAddress><registers><register><name>R</name><addressOffset>0</addressOffset><fields><field><name>F</name>{}</field></fields></register></registers></peripheral></peripherals></device>", bits);
+        assert_eq!(
+            Err("register `A.R`: field `F`: invalid bit width 2 at bit offset 9223372036854775807".to_string()),
+            parse(&field("<bitOffset>0x7fffffffffffffff</bitOffset><bitWidth>2</bitWidth>"))
+        );
+        assert!(parse(&field("<bitOffset>-9223372036854775808</bitOffset><bitWidth>0</bitWidth>")).is_err());
+        let array = "<device><peripherals><peripheral><name>A</name><baseAddress>0</baseAddress><registers><register><dim>3</dim><dimIncrement>0x4000000000000000</dimIncrement><name>R%s</name><addressOffset>0</addressOffset></register></registers></peripheral></peripherals></device>";
+        assert_eq!(Err("register `R%s`: the address offset of index 2 is out of range".to_string()), parse(array));
+    }
+}
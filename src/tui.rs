@@ -0,0 +1,279 @@
+use std::io::{self, Stdout, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::queue;
+
+use crate::command::Command;
+use crate::format::{self, FormatOptions, Radix};
+use crate::vm::VM;
+
+///a row of the grid has 16 bits, types narrower than that have a single shorter row
+const BITS_PER_ROW: u32 = 16;
+const LEFT: u16 = 2;
+const GRID_TOP: u16 = 2;
+///every row of the grid has a line for the bit indices, one for the bits and a blank line.
+const LINES_PER_ROW: u16 = 3;
+///a nibble is drawn as `0 0 0 0` followed by a gap to the next nibble
+const NIBBLE_WIDTH: u16 = 10;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Focus {
+    Input,
+    Grid,
+}
+
+/// Full-screen mode that shows the current value as a bit grid, which can be edited bit by bit
+/// with the keyboard or the mouse.
+struct Tui<'a> {
+    vm: &'a mut VM,
+    value: i64,
+    selected: u32,
+    focus: Focus,
+    input: String,
+    history: Vec<(String, i64)>,
+    message: Option<String>,
+}
+
+/// restores the terminal when the TUI is left, even if it is left by a panic.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new(stdout: &mut Stdout) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        queue!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        stdout.flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = queue!(stdout, DisableMouseCapture, LeaveAlternateScreen, Show);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// runs the TUI until the user quits it.
+pub fn run(vm: &mut VM) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let _guard = TerminalGuard::new(&mut stdout)?;
+    let value = vm.previous_result().unwrap_or(0);
+    let mut tui = Tui {
+        vm,
+        value,
+        selected: 0,
+        focus: Focus::Input,
+        input: String::new(),
+        history: Vec::new(),
+        message: None,
+    };
+    loop {
+        tui.draw(&mut stdout)?;
+        let keep_running = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => tui.handle_key(key),
+            Event::Mouse(mouse) => {
+                if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+                    if let Some(bit) = bit_at(mouse.column, mouse.row, tui.width()) {
+                        tui.selected = bit;
+                        tui.toggle(bit);
+                    }
+                }
+                true
+            }
+            _ => true,
+        };
+        if !keep_running {
+            return Ok(());
+        }
+    }
+}
+
+impl Tui<'_> {
+    ///returns `false` if the TUI should be closed.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return !matches!(key.code, KeyCode::Char('c') | KeyCode::Char('d'));
+        }
+        let width = self.width();
+        match (self.focus, key.code) {
+            (_, KeyCode::Tab | KeyCode::BackTab) => {
+                self.focus = if self.focus == Focus::Input { Focus::Grid } else { Focus::Input };
+            }
+            (Focus::Grid, KeyCode::Esc | KeyCode::Char('q')) => return false,
+            (Focus::Grid, KeyCode::Left) => self.selected = (self.selected + 1) % width,
+            (Focus::Grid, KeyCode::Right) => self.selected = (self.selected + width - 1) % width,
+            //with a single row up and down stay on the bit
+            (Focus::Grid, KeyCode::Up) => self.selected = (self.selected + BITS_PER_ROW % width) % width,
+            (Focus::Grid, KeyCode::Down) => self.selected = (self.selected + width - BITS_PER_ROW % width) % width,
+            (Focus::Grid, KeyCode::Char(' ') | KeyCode::Enter) => self.toggle(self.selected),
+            (Focus::Grid, KeyCode::Char('i')) => self.focus = Focus::Input,
+            (Focus::Input, KeyCode::Esc) => self.input.clear(),
+            (Focus::Input, KeyCode::Backspace) => {
+                self.input.pop();
+            }
+            (Focus::Input, KeyCode::Char(ch)) => self.input.push(ch),
+            (Focus::Input, KeyCode::Enter) => return self.submit(),
+            _ => {}
+        }
+        true
+    }
+
+    ///returns the number of bits of the grid, the width of the current type.
+    fn width(&self) -> u32 {
+        self.vm.int_type().width
+    }
+
+    ///flips `bit` of the current value and makes the new value the VM's previous result.
+    fn toggle(&mut self, bit: u32) {
+        self.value = self.vm.int_type().normalize(self.value ^ 1 << bit);
+        self.vm.set_previous_result(self.value);
+        self.message = None;
+    }
+
+    ///evaluates the input line, returns `false` if the TUI should be closed.
+    fn submit(&mut self) -> bool {
+        let input = std::mem::take(&mut self.input);
+        self.message = None;
+        match Command::parse(input.trim()) {
+            Ok(Command::Exit) => return false,
+            Ok(Command::Evaluate("")) => {}
+            Ok(Command::Evaluate(expression)) => match self.vm.run(expression) {
+                Ok(result) => {
+                    self.value = result;
+                    self.history.push((expression.to_string(), result));
+                }
+                Err(err) => {
+                    self.message = Some(err.to_string());
+                    self.input = input;
+                }
+            },
+            Ok(_) => self.message = Some("this command is not available in the TUI".to_string()),
+            Err(msg) => self.message = Some(msg),
+        }
+        true
+    }
+
+    fn draw(&self, stdout: &mut Stdout) -> io::Result<()> {
+        let (_, rows) = terminal::size()?;
+        queue!(stdout, Hide, Clear(ClearType::All), MoveTo(LEFT, 0), Print("Tab: switch between input and bit grid, arrows/space/click: toggle bits, Ctrl-C: quit"))?;
+
+        let width = self.width();
+        for row in 0..rows_of(width) {
+            let y = GRID_TOP + row as u16 * LINES_PER_ROW;
+            queue!(stdout, MoveTo(LEFT, y), Print(index_line(row, width)))?;
+        }
+        for bit in 0..width {
+            let (x, y) = bit_position(bit, width);
+            let is_set = self.value >> bit & 1 == 1;
+            let highlight = self.focus == Focus::Grid && bit == self.selected;
+            if is_set {
+                queue!(stdout, SetAttribute(Attribute::Bold))?;
+            } else {
+                queue!(stdout, SetAttribute(Attribute::Dim))?;
+            }
+            if highlight {
+                queue!(stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(stdout, MoveTo(x, y), Print(if is_set { '1' } else { '0' }), SetAttribute(Attribute::Reset))?;
+        }
+
+        let mut y = GRID_TOP + rows_of(width) as u16 * LINES_PER_ROW;
+        let options = FormatOptions::default();
+        for (name, radix) in [("DEC", Radix::Dec), ("HEX", Radix::Hex), ("OCT", Radix::Oct), ("BIN", Radix::Bin)] {
+            queue!(stdout, MoveTo(LEFT, y), Print(format!("{} {}", name, format::format(self.value, radix, &options, self.vm.int_type()))))?;
+            y += 1;
+        }
+
+        y += 1;
+        queue!(stdout, MoveTo(LEFT, y), Print("History"))?;
+        y += 1;
+        //the history shows the latest entries that fit between the panes and the input line
+        let free = rows.saturating_sub(y + 3) as usize;
+        let skip = self.history.len().saturating_sub(free);
+        for (expression, result) in self.history.iter().skip(skip) {
+            queue!(stdout, MoveTo(LEFT, y), Print(format!("{} = {}", expression, result)))?;
+            y += 1;
+        }
+
+        if let Some(msg) = &self.message {
+            queue!(stdout, MoveTo(LEFT, rows.saturating_sub(2)), Print(msg))?;
+        }
+        let input_line = format!(">> {}", self.input);
+        queue!(stdout, MoveTo(LEFT, rows.saturating_sub(1)), Print(&input_line))?;
+        if self.focus == Focus::Input {
+            queue!(stdout, MoveTo(LEFT + input_line.chars().count() as u16, rows.saturating_sub(1)), Show)?;
+        }
+        stdout.flush()
+    }
+}
+
+/// returns the number of rows of the grid of `width` bits.
+fn rows_of(width: u32) -> u32 {
+    width.div_ceil(BITS_PER_ROW)
+}
+
+/// returns the bit indices shown above `row` of the grid of `width` bits: the highest bit of every nibble.
+fn index_line(row: u32, width: u32) -> String {
+    let high = width - 1 - row * BITS_PER_ROW;
+    (0..BITS_PER_ROW.min(width) / 4)
+        .map(|nibble| format!("{:<width$}", high - nibble * 4, width = NIBBLE_WIDTH as usize))
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// returns the screen position (column, row) of `bit` in the grid of `width` bits.
+fn bit_position(bit: u32, width: u32) -> (u16, u16) {
+    let offset = width - 1 - bit;
+    let row = (offset / BITS_PER_ROW) as u16;
+    let in_row = offset % BITS_PER_ROW;
+    let x = LEFT + (in_row / 4) as u16 * NIBBLE_WIDTH + (in_row % 4) as u16 * 2;
+    (x, GRID_TOP + row * LINES_PER_ROW + 1)
+}
+
+/// returns the bit of the grid of `width` bits that is drawn at the screen position (column, row), if any.
+fn bit_at(column: u16, row: u16, width: u32) -> Option<u32> {
+    (0..width).find(|bit| bit_position(*bit, width) == (column, row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_line() {
+        assert_eq!("63        59        55        51", index_line(0, 64));
+        assert_eq!("15        11        7         3", index_line(3, 64));
+        assert_eq!("31        27        23        19", index_line(0, 32));
+        assert_eq!("7         3", index_line(0, 8));
+    }
+
+    #[test]
+    fn test_bit_position() {
+        assert_eq!((LEFT, GRID_TOP + 1), bit_position(63, 64));
+        assert_eq!((LEFT + 2, GRID_TOP + 1), bit_position(62, 64));
+        assert_eq!((LEFT + NIBBLE_WIDTH, GRID_TOP + 1), bit_position(59, 64));
+        assert_eq!((LEFT + 3 * NIBBLE_WIDTH + 6, GRID_TOP + 3 * LINES_PER_ROW + 1), bit_position(0, 64));
+        assert_eq!((LEFT, GRID_TOP + 1), bit_position(15, 16));
+        assert_eq!((LEFT + NIBBLE_WIDTH + 6, GRID_TOP + 1), bit_position(0, 8));
+    }
+
+    #[test]
+    fn test_bit_at() {
+        for width in [8, 16, 32, 64] {
+            for bit in 0..width {
+                let (x, y) = bit_position(bit, width);
+                assert_eq!(Some(bit), bit_at(x, y, width));
+            }
+        }
+        assert_eq!(None, bit_at(LEFT + 1, GRID_TOP + 1, 64));
+        assert_eq!(None, bit_at(LEFT, GRID_TOP, 64));
+        //the rows below the grid of a narrower type are empty
+        assert_eq!(None, bit_at(LEFT, GRID_TOP + LINES_PER_ROW + 1, 16));
+    }
+}
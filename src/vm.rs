@@ -1,12 +1,66 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::dump;
+use crate::elf::Symbol;
+use crate::error::{Error, Warning};
+use crate::functions;
+use crate::int_type::IntType;
 use crate::scanner::Token;
-use crate::parser::Parser;
+use crate::parser::{Parser, Spanned};
+
+/// Status flags of the last result, similar to the flags of a CPU.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Flags {
+    /// the result is zero
+    pub zero: bool,
+    /// the highest bit of the result is set
+    pub sign: bool,
+    /// an arithmetic operation did not fit into the current type and wrapped around
+    pub overflow: bool,
+}
+
+/// What a shift does with a count that is negative or not smaller than the word size.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ShiftMode {
+    /// all bits are shifted out, the result is 0, or -1 for an arithmetic right shift of a negative value, like on ARM
+    #[default]
+    Saturate,
+    /// only the lowest 5 bits of the count are used, 6 bits for 64 bit words, like on x86
+    Mask,
+    /// the shift is an error
+    Error,
+}
+
+impl ShiftMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "saturate" => Some(ShiftMode::Saturate),
+            "mask" => Some(ShiftMode::Mask),
+            "error" => Some(ShiftMode::Error),
+            _ => None,
+        }
+    }
+}
+
+/// the page size used by `to pages` unless it is changed
+pub const DEFAULT_PAGE_SIZE: i64 = 4096;
 
 #[derive(Debug, Clone)]
 pub struct VM {
-    parse_expression: Vec<Token>,
-    stack: Vec<i64>,
+    parse_expression: Vec<Spanned>,
+    /// the values with the type of the typed literal they were computed from, `None` for the current type
+    stack: Vec<(i64, Option<IntType>)>,
     result: i64,
     previous_result: Option<i64>,
+    int_type: IntType,
+    shift_mode: ShiftMode,
+    page_size: i64,
+    flags: Flags,
+    warnings: Vec<Warning>,
+    variables: HashMap<String, i64>,
+    /// the symbols loaded from ELF files, sorted by address
+    symbols: Vec<Symbol>,
 }
 
 impl VM {
@@ -16,53 +70,312 @@ impl VM {
             stack: Vec::new(),
             result: 0,
             previous_result: None,
+            int_type: IntType::default(),
+            shift_mode: ShiftMode::default(),
+            page_size: DEFAULT_PAGE_SIZE,
+            flags: Flags::default(),
+            warnings: Vec::new(),
+            variables: HashMap::new(),
+            symbols: Vec::new(),
         }
     }
 
-    pub fn run(&mut self, input: &str) -> i64 {
-        let mut parser = Parser::new(&input);
-        self.parse_expression.append(&mut parser.parse());
-        for item in &self.parse_expression {
-            match item {
-                Token::BinaryNumber(x) | Token::DecimalNumber(x) | Token::HexNumber(x) => self.stack.push(*x),
-                Token::PreviousResult => self.stack.push(self.previous_result.unwrap_or(0)),
+    /// returns the type every value is truncated to.
+    pub fn int_type(&self) -> IntType {
+        self.int_type
+    }
+
+    pub fn set_int_type(&mut self, int_type: IntType) {
+        self.int_type = int_type;
+        self.previous_result = self.previous_result.map(|value| int_type.normalize(value));
+    }
+
+    pub fn set_shift_mode(&mut self, shift_mode: ShiftMode) {
+        self.shift_mode = shift_mode;
+    }
+
+    /// returns the size of a page in bytes, used by `to pages` and the page helpers.
+    pub fn page_size(&self) -> i64 {
+        self.page_size
+    }
+
+    /// sets the size of a page, which has to be positive.
+    pub fn set_page_size(&mut self, page_size: i64) {
+        self.page_size = page_size;
+    }
+
+    pub fn run(&mut self, input: &str) -> Result<i64, Error> {
+        let mut parser = Parser::new(input);
+        self.stack.clear();
+        self.flags = Flags::default();
+        self.warnings.clear();
+        self.parse_expression = parser.parse_spanned()?;
+        let mut position = 0;
+        while let Some((item, span)) = self.parse_expression.get(position).cloned() {
+            position += 1;
+            match &item {
+                Token::BinaryNumber(x) | Token::DecimalNumber(x) | Token::HexNumber(x) => {
+                    self.literal(*x, None, span, input);
+                }
+                Token::TypedNumber(x, int_type) => self.literal(*x, Some(*int_type), span, input),
+                Token::PreviousResult => self.stack.push((self.previous_result.unwrap_or(0), None)),
+                //a symbol can be used like a variable, as long as there is no variable with its name
+                Token::Identifier(name) => match (self.variables.get(name), self.symbol(name)) {
+                    (Some(value), _) => self.stack.push((*value, None)),
+                    (None, Some(symbol)) => self.stack.push((self.int_type.normalize(symbol.address), None)),
+                    (None, None) => return Err(Error::UnknownVariable(span.clone(), name.clone())),
+                },
+                Token::AddressOf(name) | Token::SizeOf(name) => {
+                    let symbol = self.symbol(name).ok_or(Error::UnknownSymbol(span.clone(), name.clone()))?;
+                    let value = if matches!(item, Token::AddressOf(_)) { symbol.address } else { symbol.size };
+                    self.stack.push((self.int_type.normalize(value), None));
+                }
+                Token::Store(name) => {
+                    let (value, _) = *self.stack.last().unwrap();
+                    self.variables.insert(name.clone(), self.int_type.normalize(value));
+                }
+                Token::AndThen(skip) => {
+                    if self.stack.pop().unwrap().0 == 0 {
+                        self.stack.push((0, None));
+                        position += skip;
+                    }
+                }
+                Token::OrElse(skip) => {
+                    if self.stack.pop().unwrap().0 != 0 {
+                        self.stack.push((1, None));
+                        position += skip;
+                    }
+                }
+                Token::JumpIfZero(skip) => {
+                    if self.stack.pop().unwrap().0 == 0 {
+                        position += skip;
+                    }
+                }
+                Token::Jump(skip) => position += skip,
+                Token::ReadFile(file_name, size, big_endian) => {
+                    let offset = self.stack.pop().unwrap().0 as u64 & self.int_type.mask();
+                    let value = dump::read(file_name, offset, *size, *big_endian).map_err(|message| Error::InvalidArgument(span.clone(), message))?;
+                    if value > self.int_type.mask() {
+                        self.wrapped(span, input, self.int_type);
+                    }
+                    self.stack.push((self.int_type.normalize(value as i64), None));
+                }
+                Token::Call(name, count) => {
+                    match functions::arity(name) {
+                        None => return Err(Error::UnknownFunction(span.clone(), name.clone())),
+                        Some(arity) if !arity.contains(count) => return Err(Error::ArgumentCount(span.clone(), name.clone(), arity)),
+                        Some(_) => {}
+                    }
+                    let args: Vec<_> = self.stack.split_off(self.stack.len() - count).into_iter().map(|(value, _)| value).collect();
+                    let (result, overflow) = functions::call(name, &args, self.int_type, self.page_size).map_err(|err| err.at(span.clone()))?;
+                    if overflow {
+                        self.wrapped(span, input, self.int_type);
+                    }
+                    self.stack.push((self.int_type.normalize(result), None));
+                }
                 op if op.is_operator() => {
-                    let result;
-                    if op == &Token::Bang || op == &Token::TwosComplement || op == &Token::Minus {
-                        let val = self.stack.pop().unwrap();
-                        result = self.clone().apply_operator(op, val, 0);
+                    let ((rhs, rhs_type), (lhs, lhs_type)) = if op.is_unary() {
+                        (self.stack.pop().unwrap(), (0, None))
                     } else {
-                        let lhs = self.stack.pop().unwrap();
-                        let rhs = self.stack.pop().unwrap();
-                        result = self.clone().apply_operator(op, lhs, rhs);
+                        (self.stack.pop().unwrap(), self.stack.pop().unwrap())
+                    };
+                    //typed literals are computed at their type, the count of a shift does not change the type
+                    let result_type = match op {
+                        Token::ShiftLeft | Token::ShiftRight | Token::LogicalShiftRight | Token::RotateLeft | Token::RotateRight => lhs_type,
+                        _ => common_type(lhs_type, rhs_type),
+                    };
+                    let int_type = result_type.unwrap_or(self.int_type);
+                    let (rhs, lhs) = (int_type.normalize(rhs), int_type.normalize(lhs));
+                    let result = self.apply_operator(op, rhs, lhs, int_type, &span)?;
+                    if self.overflows(op, rhs, lhs, int_type) {
+                        self.wrapped(span, input, int_type);
                     }
-                    self.stack.push(result);
+                    let result_type = if op.is_untyped() { None } else { result_type };
+                    self.stack.push((int_type.normalize(result), result_type));
                 },
                 err => panic!("err: {:?}", err),
 
             }
         }
-        self.result = self.stack.pop().unwrap();
+        self.result = self.int_type.normalize(self.stack.pop().unwrap().0);
         self.previous_result = Some(self.result);
-        self.result
+        self.flags.zero = self.result == 0;
+        self.flags.sign = (self.result as u64) >> (self.int_type.width - 1) & 1 == 1;
+        Ok(self.result)
+    }
+
+    ///pushes the literal `value` of the `int_type` given by its suffix, or of the current type,
+    ///and warns if it does not fit.
+    fn literal(&mut self, value: i64, int_type: Option<IntType>, span: Range<usize>, input: &str) {
+        let literal_type = int_type.unwrap_or(self.int_type);
+        if value as u64 > literal_type.mask() {
+            self.warnings.push(Warning {
+                message: format!("literal `{}` does not fit into {} and was truncated", &input[span.clone()], literal_type),
+                span,
+            });
+        }
+        self.stack.push((literal_type.normalize(value), int_type));
+    }
+
+    /// defines the variable `name`, e.g. for a constant loaded from a file.
+    pub fn set_variable(&mut self, name: &str, value: i64) {
+        self.variables.insert(name.to_string(), self.int_type.normalize(value));
+    }
+
+    /// adds `symbols`, e.g. of an ELF file, which can be used as `&name`, `sizeof(name)` or just `name`.
+    /// A symbol replaces a previous one with the same name.
+    pub fn add_symbols(&mut self, symbols: Vec<Symbol>) {
+        self.symbols.retain(|known| !symbols.iter().any(|symbol| symbol.name == known.name));
+        self.symbols.extend(symbols);
+        self.symbols.sort_by_key(|symbol| symbol.address as u64);
+    }
+
+    fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// returns the symbol `address` belongs to with the offset of the address in it: the symbol that contains the
+    /// address, otherwise the closest symbol below it. `None` if there is no symbol below the address.
+    pub fn symbol_at(&self, address: i64) -> Option<(&Symbol, i64)> {
+        let address = address as u64;
+        let mut below = self.symbols.iter().rev().filter(|symbol| symbol.address as u64 <= address);
+        let containing = below.clone().find(|symbol| address - (symbol.address as u64) < symbol.size as u64);
+        containing.or(below.next()).map(|symbol| (symbol, address.wrapping_sub(symbol.address as u64) as i64))
+    }
+
+    /// returns the flags of the last `run`.
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// returns the warnings of the last `run`.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// evaluates `input` like `run` without changing the VM: the previous result, the variables, the flags and the
+    /// warnings stay as they are. For the arguments of commands like `lookup`, which are not results of their own.
+    pub fn query(&self, input: &str) -> Result<i64, Error> {
+        self.clone().run(input)
+    }
+
+    /// returns the result of the last successful `run`.
+    pub fn previous_result(&self) -> Option<i64> {
+        self.previous_result
+    }
+
+    /// overrides the previous result, e.g. after it was edited bit by bit.
+    pub fn set_previous_result(&mut self, value: i64) {
+        self.result = value;
+        self.previous_result = Some(value);
+    }
+
+    ///sets the overflow flag and warns that the result of the operation at `span` did not fit into `int_type`.
+    fn wrapped(&mut self, span: Range<usize>, input: &str, int_type: IntType) {
+        self.flags.overflow = true;
+        self.warnings.push(Warning {
+            message: format!("result of `{}` does not fit into {} and wrapped around", &input[span.clone()], int_type),
+            span,
+        });
     }
 
-    fn apply_operator(&mut self, operator: &Token, rhs: i64, lhs: i64) -> i64 {
-        match operator {
-            Token::Plus => lhs + rhs,
-            Token::Minus => lhs - rhs,
+    ///applies `operator` at `span` to the operands, which are already normalized to `int_type`.
+    ///The result is not normalized, arithmetic wraps around at 64 bits.
+    fn apply_operator(&self, operator: &Token, rhs: i64, lhs: i64, int_type: IntType, span: &Range<usize>) -> Result<i64, Error> {
+        let unsigned = !int_type.signed;
+        let result = match operator {
+            Token::Plus => lhs.wrapping_add(rhs),
+            Token::Minus => lhs.wrapping_sub(rhs),
             Token::And => lhs & rhs,
             Token::Or => lhs | rhs,
             Token::Nor => !(lhs | rhs),
             Token::Xor => lhs ^ rhs,
-            Token::ShiftLeft => lhs << rhs,
-            Token::ShiftRight => lhs >> rhs,
+            Token::ShiftLeft => ((lhs as i128) << self.shift_count(rhs, int_type, span)?) as i64,
+            //`>>` is an arithmetic shift for signed types, `>>>` always a logical one
+            Token::ShiftRight if !unsigned => (lhs as i128 >> self.shift_count(rhs, int_type, span)?) as i64,
+            Token::ShiftRight | Token::LogicalShiftRight => ((lhs as u64 & int_type.mask()) as u128 >> self.shift_count(rhs, int_type, span)?) as i64,
+            Token::RotateLeft => rotate_left(lhs, int_type.to_i128(rhs), int_type),
+            Token::RotateRight => rotate_left(lhs, -int_type.to_i128(rhs), int_type),
             Token::Bang => !rhs,
-            Token::TwosComplement => (!rhs) + 1,
-            Token::Mult => lhs * rhs,
-            Token::Modulo => lhs.rem_euclid(rhs),
+            Token::Negate => rhs.wrapping_neg(),
+            Token::TwosComplement => (!rhs).wrapping_add(1),
+            Token::Mult => lhs.wrapping_mul(rhs),
+            Token::Power => functions::power(lhs, rhs, int_type).map_err(|err| err.at(span.clone()))?.0,
+            Token::Modulo if rhs == 0 => return Err(Error::DivisionByZero(span.clone())),
+            Token::Modulo if unsigned => ((lhs as u64) % (rhs as u64)) as i64,
+            //`MIN % -1` is 0, only the quotient overflows
+            Token::Modulo => lhs.wrapping_rem_euclid(rhs),
+            Token::Equal => (lhs == rhs) as i64,
+            Token::NotEqual => (lhs != rhs) as i64,
+            Token::Less => (int_type.to_i128(lhs) < int_type.to_i128(rhs)) as i64,
+            Token::LessEqual => (int_type.to_i128(lhs) <= int_type.to_i128(rhs)) as i64,
+            Token::Greater => (int_type.to_i128(lhs) > int_type.to_i128(rhs)) as i64,
+            Token::GreaterEqual => (int_type.to_i128(lhs) >= int_type.to_i128(rhs)) as i64,
+            //the value is reinterpreted at the width of the cast, the result is normalized to the current type again
+            Token::Cast(int_type) => int_type.normalize(rhs),
+            Token::Unit(size) => rhs.wrapping_mul(*size),
+            //the number of units needed to hold the value, so it is rounded up
+            Token::ConvertTo(size) => (int_type.to_i128(rhs) + *size as i128 - 1).div_euclid(*size as i128) as i64,
+            Token::ConvertToPages => (int_type.to_i128(rhs) + self.page_size as i128 - 1).div_euclid(self.page_size as i128) as i64,
+            Token::Not => (rhs == 0) as i64,
+            Token::IsTrue => (rhs != 0) as i64,
             err => panic!("unexpected operator: {:?}", err),
+        };
+        Ok(result)
+    }
+
+    ///returns the number of bits to shift by for the shift `count` at `span`, applying the shift mode to counts out of range.
+    ///The result is at most 64, so shifts have to be done with 128 bits.
+    fn shift_count(&self, count: i64, int_type: IntType, span: &Range<usize>) -> Result<u32, Error> {
+        let width = int_type.width;
+        let count = int_type.to_i128(count);
+        if (0..width as i128).contains(&count) {
+            return Ok(count as u32);
+        }
+        match self.shift_mode {
+            ShiftMode::Saturate => Ok(width),
+            ShiftMode::Mask => Ok(count as u32 & (width.max(32) - 1)),
+            ShiftMode::Error => Err(Error::InvalidArgument(span.clone(), format!("shift count {} is out of range for {}", count, int_type))),
+        }
+    }
+
+    ///`true` if the exact result of an arithmetic `operator` is out of the range of `int_type`.
+    fn overflows(&self, operator: &Token, rhs: i64, lhs: i64, int_type: IntType) -> bool {
+        if operator == &Token::Power {
+            return functions::power(lhs, rhs, int_type).is_ok_and(|(_, overflow)| overflow);
         }
+        let (lhs, rhs) = (int_type.to_i128(lhs), int_type.to_i128(rhs));
+        let exact = match operator {
+            Token::Plus => lhs.checked_add(rhs),
+            Token::Minus => lhs.checked_sub(rhs),
+            Token::Mult => lhs.checked_mul(rhs),
+            Token::Unit(size) => rhs.checked_mul(*size as i128),
+            //negating an unsigned value is the usual way to get its two's complement, not an overflow
+            Token::Negate if int_type.signed => rhs.checked_neg(),
+            _ => return false,
+        };
+        !exact.is_some_and(|exact| int_type.contains(exact))
+    }
+}
+
+///rotates the bits of `value` within the word size, a negative `count` rotates to the right.
+fn rotate_left(value: i64, count: i128, int_type: IntType) -> i64 {
+    let width = int_type.width;
+    let value = value as u64 & int_type.mask();
+    let count = count.rem_euclid(width as i128) as u32;
+    if count == 0 {
+        return value as i64;
+    }
+    ((value << count | value >> (width - count)) & int_type.mask()) as i64
+}
+
+///returns the type an operation with operands of the given types is computed at, like the usual arithmetic
+///conversions of C: the wider type, or the unsigned one for types of the same width. `None` is the current type.
+fn common_type(lhs: Option<IntType>, rhs: Option<IntType>) -> Option<IntType> {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) if lhs.width != rhs.width => Some(if lhs.width > rhs.width { lhs } else { rhs }),
+        (Some(lhs), Some(rhs)) => Some(IntType { width: lhs.width, signed: lhs.signed && rhs.signed }),
+        (int_type, None) | (None, int_type) => int_type,
     }
 }
 
@@ -73,75 +386,329 @@ mod tests {
     #[test]
     fn test_1_plus_1() {
         let mut vm = VM::new();
-        assert_eq!(1+1, vm.run("1+1"));
+        assert_eq!(1+1, vm.run("1+1").unwrap());
     }
 
 
     #[test]
     fn test_nested() {
         let mut vm = VM::new();
-        assert_eq!((1&(2+3)&(4+5)), vm.run("1 and 2 + 3 and 4 + 5"));
+        assert_eq!((1&(2+3)&(4+5)), vm.run("1 and 2 + 3 and 4 + 5").unwrap());
     }
 
     #[test]
     fn test_extended() {
         let mut vm = VM::new();
-        assert_eq!(1+2+3+4+5, vm.run("1 + 2 + 3 + 4 + 5"));
+        assert_eq!(1+2+3+4+5, vm.run("1 + 2 + 3 + 4 + 5").unwrap());
     }
 
     #[test]
     fn test_expression() {
         let mut vm = VM::new();
-        assert_eq!(0b01 << 2, vm.run("0b01 << 2"));
+        assert_eq!(0b01 << 2, vm.run("0b01 << 2").unwrap());
     }
 
     #[test]
     fn test_expression_2() {
         let mut vm = VM::new();
-        assert_eq!(1 + 2 + 3 - 4, vm.run("1+2+3-4"));
+        assert_eq!(1 + 2 + 3 - 4, vm.run("1+2+3-4").unwrap());
     }
 
     #[test]
     fn test_hex_expr() {
         let mut vm = VM::new();
-        assert_eq!(0xff & 0xf1, vm.run("0xff&0xf1"));
+        assert_eq!(0xff & 0xf1, vm.run("0xff&0xf1").unwrap());
     }
 
     #[test]
     fn test_unary() {
         let mut vm = VM::new();
-        assert_eq!(!1_i64, vm.run("!1"));
+        assert_eq!(!1_i64, vm.run("!1").unwrap());
     }
 
     #[test]
     fn test_twos_complement() {
         let mut vm = VM::new();
-        assert_eq!(!(1_i64)+1, vm.run("~1"));
+        assert_eq!(!(1_i64)+1, vm.run("~1").unwrap());
     }
 
     #[test]
     fn test_mult() {
         let mut vm = VM::new();
-        assert_eq!(5*3, vm.run("5*3"));
+        assert_eq!(5*3, vm.run("5*3").unwrap());
     }
 
     #[test]
     fn test_precedence() {
         let mut vm = VM::new();
-        assert_eq!((5&5)+(15&7), vm.run("(5 & 5)+(15 and 7)"));
+        assert_eq!((5&5)+(15&7), vm.run("(5 & 5)+(15 and 7)").unwrap());
     }
 
     #[test]
     fn test_zeros() {
         let mut vm = VM::new();
-        println!("vm runs on 0: {:?}", vm.run("0"));
-        assert_eq!(0, vm.run("0"));
+        println!("vm runs on 0: {:?}", vm.run("0").unwrap());
+        assert_eq!(0, vm.run("0").unwrap());
     }
 
     #[test]
     fn test_negative() {
         let mut vm = VM::new();
-        assert_eq!(1-2, vm.run("1-2"));
-        assert_eq!(-1*2, vm.run("-1*2"));
+        assert_eq!(1-2, vm.run("1-2").unwrap());
+        assert_eq!(-2, vm.run("-1*2").unwrap());
+    }
+
+    #[test]
+    fn test_width() {
+        let mut vm = VM::new();
+        vm.set_int_type(IntType::new(8, false).unwrap());
+        assert_eq!(0xff, vm.run("0 - 1").unwrap());
+        assert_eq!(0x0f, vm.run("0xff + 0x10").unwrap());
+        assert_eq!(0x7f, vm.run("0xfe >> 1").unwrap());
+        vm.set_int_type(IntType::new(8, true).unwrap());
+        assert_eq!(-1, vm.run("0xfe >> 1 | 0x80").unwrap());
+        assert_eq!(-128, vm.run("127 + 1").unwrap());
+        vm.set_int_type(IntType::new(64, false).unwrap());
+        assert_eq!(0x7fff_ffff_ffff_ffff, vm.run("-1 >> 1").unwrap());
+    }
+
+    #[test]
+    fn test_modulo_by_zero() {
+        let mut vm = VM::new();
+        assert_eq!(Err(Error::DivisionByZero(2..3)), vm.run("5 % 0"));
+        assert_eq!(Err(Error::DivisionByZero(21..22)), vm.run("-9223372036854775808 % 0"));
+    }
+
+    #[test]
+    fn test_modulo_overflow() {
+        let mut vm = VM::new();
+        assert_eq!(Ok(0), vm.run("-9223372036854775808 % -1"));
+        assert_eq!(Ok(2), vm.run("-9223372036854775808 % 5"));
+        vm.set_int_type(IntType::new(8, true).unwrap());
+        assert_eq!(Ok(0), vm.run("-128 % -1"));
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut vm = VM::new();
+        assert_eq!(4096, vm.run("page_size = 1 << 12").unwrap());
+        assert_eq!(4096, vm.run("page_size").unwrap());
+        assert_eq!(3 * 4096, vm.run("3 * page_size").unwrap());
+        assert_eq!(Err(Error::UnknownVariable(4..9, "pages".to_string())), vm.run("3 * pages"));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut vm = VM::new();
+        assert_eq!(1, vm.run("0x81 & 0x80 != 0").unwrap());
+        assert_eq!(0, vm.run("3 == 4").unwrap());
+        assert_eq!(1, vm.run("-1 < 0 && 2 >= 2 && not 0").unwrap());
+        assert_eq!(0, vm.run("1 <= 0 || 3 > 4").unwrap());
+        assert_eq!(20, vm.run("5 > 4 ? 20 : 30").unwrap());
+        assert_eq!(3, vm.run("0 ? 1 : 0 ? 2 : 3").unwrap());
+        vm.set_int_type(IntType::new(8, false).unwrap());
+        assert_eq!(0, vm.run("0xff < 0").unwrap());
+    }
+
+    #[test]
+    fn test_power() {
+        let mut vm = VM::new();
+        assert_eq!(243, vm.run("3**5").unwrap());
+        assert_eq!(2 * 81, vm.run("2 * 3 ** 2 ** 2").unwrap());
+        assert_eq!(1 << 20, vm.run("pow(2, 20)").unwrap());
+        assert_eq!(445, vm.run("powmod(4, 13, 497)").unwrap());
+        assert!(vm.warnings().is_empty());
+        vm.set_int_type(IntType::new(16, false).unwrap());
+        assert_eq!(0, vm.run("2 ** 16").unwrap());
+        assert!(vm.flags().overflow);
+        assert_eq!("result of `**` does not fit into u16 and wrapped around", vm.warnings()[0].message);
+        vm.run("pow(3, 11)").unwrap();
+        assert_eq!(0..3, vm.warnings()[0].span);
+        assert_eq!(Err(Error::InvalidArgument(2..4, "the exponent must not be negative".to_string())), VM::new().run("2 ** -1"));
+        assert_eq!(Err(Error::UnknownFunction(0..3, "pwr".to_string())), vm.run("pwr(2, 3)"));
+        assert_eq!(Err(Error::ArgumentCount(0..6, "powmod".to_string(), 3..=3)), vm.run("powmod(2, 3)"));
+        assert_eq!(Err(Error::DivisionByZero(0..6)), vm.run("powmod(2, 3, 0)"));
+    }
+
+    #[test]
+    fn test_byte_order() {
+        let mut vm = VM::new();
+        vm.set_int_type(IntType::new(32, false).unwrap());
+        assert_eq!(Ok(0x78563412), vm.run("to_be(0x12345678)"));
+        assert_eq!(Ok(0x12345678), vm.run("to_le(0x12345678)"));
+        assert_eq!(Ok(0x12345678), vm.run("from_bytes(0x12, 0x34, 0x56, 0x78)"));
+        assert!(vm.warnings().is_empty());
+        assert_eq!(Ok(0x3456789a), vm.run("from_bytes(0x12, 0x34, 0x56, 0x78, 0x9a)"));
+        assert_eq!("result of `from_bytes` does not fit into u32 and wrapped around", vm.warnings()[0].message);
+        assert_eq!(Err(Error::ArgumentCount(0..10, "from_bytes".to_string(), 1..=8)), vm.run("from_bytes()"));
+        assert_eq!("`from_bytes` expects 1 to 8 arguments", vm.run("from_bytes()").unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_shifts_and_rotations() {
+        let mut vm = VM::new();
+        assert_eq!(i64::MIN >> 4, vm.run("0x8000000000000000 >> 4").unwrap());
+        assert_eq!(0x0800000000000000, vm.run("0x8000000000000000 >>> 4").unwrap());
+        assert_eq!(0x1800000000000000, vm.run("0x8000000000000001 >>>> 4").unwrap());
+        assert_eq!(0x18, vm.run("0x8000000000000001 <<< 4").unwrap());
+        assert_eq!(0x18, vm.run("0x8000000000000001 ror -4").unwrap());
+        vm.set_int_type(IntType::new(8, false).unwrap());
+        assert_eq!(0x1e, vm.run("0xf0 rol 5").unwrap());
+        assert_eq!(0x87, vm.run("0x0f ROR 1").unwrap());
+        assert_eq!(0x0f, vm.run("0x0f rol 8").unwrap());
+        vm.set_int_type(IntType::new(8, true).unwrap());
+        assert_eq!(-8, vm.run("0x80 >> 4").unwrap());
+        assert_eq!(0x08, vm.run("0x80 >>> 4").unwrap());
+    }
+
+    #[test]
+    fn test_shift_modes() {
+        let mut vm = VM::new();
+        assert_eq!(0, vm.run("1 << 64").unwrap());
+        assert_eq!(-1, vm.run("-8 >> 100").unwrap());
+        assert_eq!(0, vm.run("1 << -1").unwrap());
+        vm.set_shift_mode(ShiftMode::Mask);
+        assert_eq!(2, vm.run("1 << 65").unwrap());
+        assert_eq!(i64::MIN, vm.run("1 << -1").unwrap());
+        vm.set_int_type(IntType::new(8, false).unwrap());
+        //like x86, the count is masked to 5 bits for words smaller than 64 bits
+        assert_eq!(0, vm.run("1 << 9").unwrap());
+        assert_eq!(2, vm.run("1 << 33").unwrap());
+        vm.set_shift_mode(ShiftMode::Error);
+        assert_eq!(0x80, vm.run("1 << 7").unwrap());
+        assert_eq!(Err(Error::InvalidArgument(2..4, "shift count 8 is out of range for u8".to_string())), vm.run("1 << 8"));
+    }
+
+    #[test]
+    fn test_casts() {
+        let mut vm = VM::new();
+        assert_eq!(-1, vm.run("(i8)0xff").unwrap());
+        assert_eq!(0xff, vm.run("(u8)-1").unwrap());
+        assert_eq!(0x1ff, vm.run("(u8)-1 + (u16)0x100").unwrap());
+        assert_eq!(0x34, vm.run("0x1234 as u8").unwrap());
+        assert_eq!(-0x80, vm.run("0x80 as i8 as i32").unwrap());
+        assert_eq!(-128, vm.run("sext(0x80, 8)").unwrap());
+        assert_eq!(0xfff, vm.run("zext(-1, 12)").unwrap());
+        assert_eq!(-1, vm.run("trunc(0x1fff, 12)").unwrap());
+        vm.set_int_type(IntType::new(16, false).unwrap());
+        assert_eq!(0xff80, vm.run("sext(0x80, 8)").unwrap());
+        assert_eq!(0xfff, vm.run("trunc(0x1fff, 12)").unwrap());
+        assert_eq!(0x5678, vm.run("(u32)0x12345678").unwrap());
+        assert_eq!(Err(Error::InvalidArgument(0..4, "the bit width has to be between 1 and 64".to_string())), vm.run("sext(1, 0)"));
+    }
+
+    #[test]
+    fn test_typed_literals() {
+        let mut vm = VM::new();
+        assert_eq!(0, vm.run("0xFFu8 + 1").unwrap());
+        assert_eq!("result of `+` does not fit into u8 and wrapped around", vm.warnings()[0].message);
+        assert_eq!(-32768, vm.run("32767_i16 + 1").unwrap());
+        //the wider type wins, for the same width the unsigned one
+        assert_eq!(0x100, vm.run("0xffu8 + 1u16").unwrap());
+        assert_eq!(0xffff, vm.run("-1i16 + 0u16").unwrap());
+        assert_eq!(0xfe, vm.run("0xffu8 * 2").unwrap());
+        assert_eq!(0xf0, vm.run("0xffu8 << 4").unwrap());
+        assert_eq!(1, vm.run("0xffu8 + 1 == 0").unwrap());
+        assert!(vm.run("1UL << 40").unwrap() == 1 << 40 && vm.warnings().is_empty());
+        vm.run("0x100u8").unwrap();
+        assert_eq!("literal `0x100u8` does not fit into u8 and was truncated", vm.warnings()[0].message);
+        //the result has the current type
+        vm.set_int_type(IntType::new(8, true).unwrap());
+        assert_eq!(0x34, vm.run("0x1234u16").unwrap());
+        assert_eq!(-1, vm.run("0xffu8").unwrap());
+    }
+
+    #[test]
+    fn test_query() {
+        let mut vm = VM::new();
+        vm.run("x = 40").unwrap();
+        assert_eq!(Ok(42), vm.query("_ + 2"));
+        assert_eq!(Ok(3), vm.query("y = 3"));
+        assert_eq!(Some(40), vm.previous_result());
+        assert!(vm.run("y").is_err());
+        //the fields of `encode` are evaluated as queries, so only the encoded value becomes the previous result
+        let register = crate::registers::parse("[CR1]\nwidth = 16\n[CR1.UE]\nbits = 13\n[CR1.PS]\nbits = \"10:9\"\n").unwrap().remove(0);
+        assert_eq!(Ok(0x2400), register.encode(&[("UE", "1"), ("PS", "x % 38")], |expression| vm.query(expression).map_err(|err| err.to_string())));
+        assert_eq!(Some(40), vm.previous_result());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut vm = VM::new();
+        let symbol = |name: &str, address, size| Symbol { name: name.to_string(), address, size };
+        vm.add_symbols(vec![symbol("main", 0x1040, 0x20), symbol("g_buffer", 0x4000, 0x100), symbol("_start", 0x1000, 0)]);
+        assert_eq!(0x1040, vm.run("&main").unwrap());
+        assert_eq!(0x1048, vm.run("main + 8").unwrap());
+        assert_eq!(0x4100, vm.run("&g_buffer + sizeof(g_buffer)").unwrap());
+        vm.run("main = 1").unwrap();
+        assert_eq!(1, vm.run("main").unwrap());
+        assert_eq!(0x1040, vm.run("&main").unwrap());
+        assert_eq!(Err(Error::UnknownSymbol(9..18, "x".to_string())), vm.run("&main +  sizeof(x)"));
+
+        let lookup = |address| vm.symbol_at(address).map(|(symbol, offset)| (symbol.name.clone(), offset));
+        assert_eq!(Some(("main".to_string(), 0x10)), lookup(0x1050));
+        assert_eq!(Some(("_start".to_string(), 0x8)), lookup(0x1008));
+        assert_eq!(Some(("g_buffer".to_string(), 0x200)), lookup(0x4200));
+        assert_eq!(None, lookup(0x10));
+        //the address of `lookup` is evaluated as query, so the previous result stays
+        assert_eq!(Ok(0x4010), vm.query("&g_buffer + 0x10"));
+        assert_eq!(Some(0x1040), vm.previous_result());
+    }
+
+    #[test]
+    fn test_read_file() {
+        let path = std::env::temp_dir().join(format!("ws_calculator_vm_{}.bin", std::process::id()));
+        std::fs::write(&path, [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0]).unwrap();
+        let file_name = path.to_str().unwrap();
+        let mut vm = VM::new();
+        assert_eq!(0x3412, vm.run(&format!("read_u16(\"{}\", 0)", file_name)).unwrap());
+        assert_eq!(0x9abcdef0, vm.run(&format!("read_u32_be(\"{}\", 2 + 2)", file_name)).unwrap());
+        assert_eq!(0x78, vm.run(&format!("read_u8(\"{}\", 3)", file_name)).unwrap());
+        vm.set_int_type(IntType::new(32, false).unwrap());
+        assert_eq!(0x78563412, vm.run(&format!("read_u64_le(\"{}\", 0)", file_name)).unwrap());
+        assert_eq!(1, vm.warnings().len());
+        assert_eq!(Some(0..8), vm.run(&format!("read_u32(\"{}\", 6)", file_name)).unwrap_err().span());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_units() {
+        let mut vm = VM::new();
+        assert_eq!(4 * 1024 * 1024, vm.run("4 MiB").unwrap());
+        assert_eq!(6 << 20, vm.run("4MiB + 2048 KiB").unwrap());
+        assert_eq!(2_000_000, vm.run("2 MB").unwrap());
+        assert_eq!(2 << 40, vm.run("(1 + 1) TiB").unwrap());
+        assert_eq!(1024, vm.run("4 MiB to pages").unwrap());
+        assert_eq!(1025, vm.run("4 MiB + 1 to pages").unwrap());
+        assert_eq!(3, vm.run("2049 to KiB").unwrap());
+        vm.set_page_size(16 * 1024);
+        assert_eq!(256, vm.run("4 MiB to pages").unwrap());
+        vm.set_int_type(IntType::new(32, false).unwrap());
+        vm.run("4 GiB").unwrap();
+        assert_eq!("result of `GiB` does not fit into u32 and wrapped around", vm.warnings()[0].message);
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let mut vm = VM::new();
+        //the rhs would fail with a division by zero if it was evaluated
+        assert_eq!(0, vm.run("0 && 1 % 0").unwrap());
+        assert_eq!(1, vm.run("5 || 1 % 0").unwrap());
+        assert_eq!(1, vm.run("1 ? 1 : 1 % 0").unwrap());
+        assert_eq!(Err(Error::DivisionByZero(7..8)), vm.run("1 && 1 % 0"));
+    }
+
+    #[test]
+    fn test_flags_and_warnings() {
+        let mut vm = VM::new();
+        vm.set_int_type(IntType::new(8, true).unwrap());
+        vm.run("100 + 100").unwrap();
+        assert_eq!(Flags { zero: false, sign: true, overflow: true }, vm.flags());
+        assert_eq!(4..5, vm.warnings()[0].span);
+        vm.run("0x100").unwrap();
+        assert_eq!(Flags { zero: true, sign: false, overflow: false }, vm.flags());
+        assert_eq!("literal `0x100` does not fit into i8 and was truncated", vm.warnings()[0].message);
+        vm.run("1 + 1").unwrap();
+        assert!(vm.warnings().is_empty());
     }
 }
@@ -0,0 +1,105 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crossterm::style::Stylize;
+
+use crate::format;
+use crate::int_type::IntType;
+use crate::script;
+use crate::vm::VM;
+
+/// how often the file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// re-runs the script `file_name` every time it is saved and redraws the results, every run starts with a copy of `vm`.
+/// Results that differ from the previous run are highlighted. Runs until the process is interrupted.
+pub fn run(file_name: &str, vm: &VM) -> io::Result<()> {
+    let mut last_change = None;
+    let mut previous = Vec::new();
+    loop {
+        //editors often save by replacing the file, so a missing file is not an error, just no change
+        let change = std::fs::metadata(file_name).ok().map(|metadata| (metadata.modified().ok(), metadata.len()));
+        if change.is_some() && change != last_change {
+            last_change = change;
+            if let Ok(source) = std::fs::read_to_string(file_name) {
+                let mut vm = vm.clone();
+                let int_type = vm.int_type();
+                let results = evaluate(&mut vm, &source);
+                let mut stdout = io::stdout();
+                //clear the screen and move the cursor to the top left corner
+                write!(stdout, "\x1b[2J\x1b[H")?;
+                writeln!(stdout, "{}", format!("watching {} (Ctrl-C to stop)", file_name).dim())?;
+                writeln!(stdout, "{}", render(&source, &results, &previous, int_type))?;
+                stdout.flush()?;
+                previous = results.iter().map(|(_, result)| result.clone().ok().flatten()).collect();
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The outcome of a statement: the value, `None` for a check that holds, or the error message with the byte offset it refers to.
+type Outcome<'a> = (script::Statement<'a>, Result<Option<i64>, (usize, String)>);
+
+fn evaluate<'a>(vm: &mut VM, source: &'a str) -> Vec<Outcome<'a>> {
+    script::statements(source)
+        .into_iter()
+        .map(|statement| {
+            let result = script::execute(vm, statement.text).map_err(|err| {
+                let span_start = err.span().map_or(statement.text.len(), |span| span.start);
+                (statement.offset + span_start, err.to_string())
+            });
+            (statement, result)
+        })
+        .collect()
+}
+
+/// renders one line per statement. A statement whose value differs from the value at the same
+/// position in the `previous` run is marked with `*` and highlighted.
+fn render(source: &str, results: &[Outcome], previous: &[Option<i64>], int_type: IntType) -> String {
+    let width = results.iter().map(|(statement, _)| statement.text.chars().count()).max().unwrap_or(0);
+    let lines: Vec<String> = results
+        .iter()
+        .enumerate()
+        .map(|(index, (statement, result))| match result {
+            Ok(None) => format!("  {:<width$}  => ok", statement.text, width = width),
+            Ok(Some(value)) => {
+                let line = format!("{:<width$}  => {}", statement.text, format::summary(*value, int_type), width = width);
+                let changed = !previous.is_empty() && previous.get(index).is_none_or(|old| *old != Some(*value));
+                if changed {
+                    format!("* {}", line.yellow().bold())
+                } else {
+                    format!("  {}", line)
+                }
+            }
+            Err((offset, message)) => {
+                let (line, column) = script::location(source, *offset);
+                format!("  {}", format!("{}:{}: error: {}", line, column, message).red())
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_highlights_changes() {
+        let old_source = "a = 4\nb = a * 2\nc = 1";
+        let new_source = "a = 8\nb = a * 2\nc = 1\nd = c % 0";
+        let old = evaluate(&mut VM::new(), old_source);
+        let new = evaluate(&mut VM::new(), new_source);
+
+        let first_run = render(old_source, &old, &[], IntType::I64);
+        assert!(first_run.lines().all(|line| line.starts_with("  ")));
+
+        let previous = [Some(4), Some(8), Some(1)];
+        let lines: Vec<_> = render(new_source, &new, &previous, IntType::I64).lines().map(str::to_string).collect();
+        assert!(lines[0].starts_with("* ") && lines[0].contains("a = 8"));
+        assert!(lines[1].starts_with("* ") && lines[1].contains("0x10 (16)"));
+        assert_eq!("  c = 1      => 0x1 (1)", lines[2]);
+        assert!(lines[3].contains("4:7: error: division by zero"));
+    }
+}
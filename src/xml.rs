@@ -0,0 +1,230 @@
+/// An element of an XML document with its attributes, child elements and the text between them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    /// returns the first child element called `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// returns every child element called `name`.
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// returns the text of the first child element called `name` without surrounding whitespace.
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.trim())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// parses an XML document and returns its root element. Only what's needed for data files is supported:
+/// elements, attributes, text, CDATA sections and the predefined and numeric entities. The declaration, comments,
+/// processing instructions and the document type are skipped. Errors start with the line they refer to.
+pub fn parse(source: &str) -> Result<Element, String> {
+    let mut parser = Parser { source, position: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.position < source.len() {
+        return Err(parser.error("unexpected content after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    /// byte offset of the next character
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.source[..self.position].matches('\n').count() + 1;
+        format!("{}: {}", line, message)
+    }
+
+    /// skips `prefix` if the rest starts with it.
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest().starts_with(prefix);
+        if found {
+            self.position += prefix.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, prefix: &str) -> Result<(), String> {
+        if self.eat(prefix) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", prefix)))
+        }
+    }
+
+    /// returns the text up to `end` and skips it including `end`.
+    fn until(&mut self, end: &str) -> Result<&str, String> {
+        let length = self.rest().find(end).ok_or(self.error(&format!("expected `{}`", end)))?;
+        let text = &self.source[self.position..self.position + length];
+        self.position += length + end.len();
+        Ok(text)
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.position = self.source.len() - self.rest().trim_start().len();
+    }
+
+    /// skips whitespace, comments, processing instructions and the document type.
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.eat("<?") {
+                self.until("?>")?;
+            } else if self.eat("<!--") {
+                self.until("-->")?;
+            } else if self.rest().starts_with("<!") && !self.rest().starts_with("<![CDATA[") {
+                self.until(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.rest().find(|ch: char| !(ch.is_alphanumeric() || matches!(ch, '_' | '-' | ':' | '.'))).unwrap_or(self.rest().len());
+        if length == 0 {
+            return Err(self.error("expected a name"));
+        }
+        let name = self.rest()[..length].to_string();
+        self.position += length;
+        Ok(name)
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        self.expect("<")?;
+        let mut element = Element { name: self.name()?, ..Element::default() };
+        loop {
+            self.skip_whitespace();
+            if self.eat("/>") {
+                return Ok(element);
+            }
+            if self.eat(">") {
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.eat("\"") { "\"" } else { self.expect("'").map(|_| "'")? };
+            let value = unescape(self.until(quote)?);
+            element.attributes.push((key, value));
+        }
+        loop {
+            let text_length = self.rest().find('<').ok_or(self.error(&format!("element `{}` is not closed", element.name)))?;
+            element.text.push_str(&unescape(&self.rest()[..text_length]));
+            self.position += text_length;
+            if self.eat("</") {
+                let start = self.position;
+                if self.name()? != element.name {
+                    self.position = start;
+                    return Err(self.error(&format!("expected `</{}>`", element.name)));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if self.eat("<![CDATA[") {
+                let text = self.until("]]>")?.to_string();
+                element.text.push_str(&text);
+            } else if self.eat("<!--") {
+                self.until("-->")?;
+            } else if self.eat("<?") {
+                self.until("?>")?;
+            } else {
+                element.children.push(self.element()?);
+            }
+        }
+    }
+}
+
+/// replaces the predefined entities like `&amp;` and character references like `&#x41;` in `text`.
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let replacement = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => match reference.strip_prefix("#x").or(reference.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => reference.strip_prefix('#').and_then(|decimal| decimal.parse().ok()).and_then(char::from_u32),
+            },
+        };
+        match replacement {
+            Some(ch) => {
+                output.push(ch);
+                rest = &rest[end + 1..];
+            }
+            //an unknown entity is kept as it is
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let document = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- a comment -->
+<device schemaVersion='1.1'>
+  <name>STM32F4</name>
+  <description><![CDATA[a <b> c]]> &amp; d &#x41;</description>
+  <empty/>
+  <peripheral derivedFrom="GPIOA"><name>GPIOB</name></peripheral>
+</device>
+"#;
+        let device = parse(document).unwrap();
+        assert_eq!("device", device.name);
+        assert_eq!(Some("1.1"), device.attribute("schemaVersion"));
+        assert_eq!(Some("STM32F4"), device.child_text("name"));
+        assert_eq!(Some("a <b> c & d A"), device.child_text("description"));
+        assert_eq!(Some(""), device.child_text("empty"));
+        let peripheral = device.children("peripheral").next().unwrap();
+        assert_eq!(Some("GPIOA"), peripheral.attribute("derivedFrom"));
+        assert_eq!(Some("GPIOB"), peripheral.child_text("name"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err("2: expected `</b>`".to_string()), parse("<a><b>\n</a>"));
+        assert_eq!(Err("1: element `a` is not closed".to_string()), parse("<a>text"));
+        assert_eq!(Err("1: unexpected content after the root element".to_string()), parse("<a/><b/>"));
+        assert!(parse("<a b=c/>").is_err());
+    }
+}